@@ -0,0 +1,103 @@
+//! Compares the `Box`-based `BST` with the arena-backed `ArenaBST`.
+//!
+//! Run with `cargo bench`. Keys are inserted in a scrambled but reproducible
+//! order, then looked up in a different order and iterated.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bst::{ArenaBST, BST};
+
+const LEN: u64 = 200_000;
+const ROUNDS: u32 = 5;
+
+/// A permutation of `0..LEN`, since `LEN` is coprime with the multiplier.
+fn scrambled(multiplier: u64) -> impl Iterator<Item = u64> {
+    (0..LEN).map(move |i| (i * multiplier) % LEN)
+}
+
+/// Returns the fastest of `ROUNDS` runs of `f`.
+fn time<R>(mut f: impl FnMut() -> R) -> Duration {
+    (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .expect("at least one round")
+}
+
+fn report(operation: &str, boxed: Duration, arena: Duration) {
+    println!(
+        "{operation:<10} box {:>8.2} ms   arena {:>8.2} ms   ratio {:.2}",
+        boxed.as_secs_f64() * 1e3,
+        arena.as_secs_f64() * 1e3,
+        arena.as_secs_f64() / boxed.as_secs_f64(),
+    );
+}
+
+fn main() {
+    println!("{LEN} keys, best of {ROUNDS} runs");
+
+    let boxed_insert = time(|| {
+        let mut bst = BST::new();
+        scrambled(7919).for_each(|key| {
+            bst.insert(key);
+        });
+        bst
+    });
+    let arena_insert = time(|| {
+        let mut bst = ArenaBST::new();
+        scrambled(7919).for_each(|key| {
+            bst.insert(key);
+        });
+        bst
+    });
+    report("insert", boxed_insert, arena_insert);
+
+    let boxed: BST<u64> = scrambled(7919).collect();
+    let arena: ArenaBST<u64> = scrambled(7919).collect();
+
+    let boxed_lookup = time(|| scrambled(104_729).filter(|key| boxed.contains(key)).count());
+    let arena_lookup = time(|| scrambled(104_729).filter(|key| arena.contains(key)).count());
+    report("lookup", boxed_lookup, arena_lookup);
+
+    let boxed_iter = time(|| boxed.iter().sum::<u64>());
+    let arena_iter = time(|| arena.iter().sum::<u64>());
+    report("iterate", boxed_iter, arena_iter);
+
+    let boxed_remove = time(|| {
+        let mut bst = boxed.iter().copied().collect::<BST<u64>>();
+        scrambled(104_729).for_each(|key| {
+            bst.remove(&key);
+        });
+        bst
+    });
+    let arena_remove = time(|| {
+        let mut bst = arena.iter().copied().collect::<ArenaBST<u64>>();
+        scrambled(104_729).for_each(|key| {
+            bst.remove(&key);
+        });
+        bst
+    });
+    report("remove", boxed_remove, arena_remove);
+
+    let boxed_churn = time(|| {
+        let mut bst = boxed.iter().copied().collect::<BST<u64>>();
+        for key in scrambled(7919).take(LEN as usize / 2) {
+            bst.remove(&key);
+            bst.insert(key + LEN);
+        }
+        bst
+    });
+    let arena_churn = time(|| {
+        let mut bst = arena.iter().copied().collect::<ArenaBST<u64>>();
+        for key in scrambled(7919).take(LEN as usize / 2) {
+            bst.remove(&key);
+            bst.insert(key + LEN);
+        }
+        bst
+    });
+    report("churn", boxed_churn, arena_churn);
+}
@@ -0,0 +1,418 @@
+use std::cmp::{max, min, Ordering};
+use std::iter::{FusedIterator, Peekable};
+use std::ops::{BitAnd, BitOr, BitXor, Sub};
+
+use crate::{Augment, BSTRefIter, Comparator, Link, Node, BST};
+
+/// Walks two sorted iterators in step, pairing up equal values.
+struct MergeIter<'a, T, C, A> {
+    left: Peekable<BSTRefIter<'a, T, A>>,
+    right: Peekable<BSTRefIter<'a, T, A>>,
+    cmp: &'a C,
+}
+
+impl<'a, T, C: Comparator<T>, A> MergeIter<'a, T, C, A> {
+    fn new(left: &'a BST<T, C, A>, right: &'a BST<T, C, A>) -> MergeIter<'a, T, C, A> {
+        MergeIter {
+            left: left.iter().peekable(),
+            right: right.iter().peekable(),
+            cmp: &left.comparator,
+        }
+    }
+    /// Returns the smaller of the next values, or both if they are equal.
+    fn next_pair(&mut self) -> (Option<&'a T>, Option<&'a T>) {
+        let order = match (self.left.peek(), self.right.peek()) {
+            (Some(left), Some(right)) => self.cmp.compare(left, right),
+            (Some(_), None) => Ordering::Less,
+            (None, _) => Ordering::Greater,
+        };
+        match order {
+            Ordering::Less => (self.left.next(), None),
+            Ordering::Greater => (None, self.right.next()),
+            Ordering::Equal => (self.left.next(), self.right.next()),
+        }
+    }
+    fn lens(&self) -> (usize, usize) {
+        (self.left.len(), self.right.len())
+    }
+}
+
+/// Lazy iterator over the values in either of two `BST`s, obtained from
+/// `BST::union`.
+pub struct Union<'a, T, C, A = ()> {
+    inner: MergeIter<'a, T, C, A>,
+}
+
+impl<'a, T, C: Comparator<T>, A> Iterator for Union<'a, T, C, A> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        let (left, right) = self.inner.next_pair();
+        left.or(right)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (left, right) = self.inner.lens();
+        (max(left, right), Some(left + right))
+    }
+}
+
+impl<'a, T, C: Comparator<T>, A> FusedIterator for Union<'a, T, C, A> {}
+
+/// Lazy iterator over the values in both of two `BST`s, obtained from
+/// `BST::intersection`.
+pub struct Intersection<'a, T, C, A = ()> {
+    inner: MergeIter<'a, T, C, A>,
+}
+
+impl<'a, T, C: Comparator<T>, A> Iterator for Intersection<'a, T, C, A> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        while self.inner.left.peek().is_some() && self.inner.right.peek().is_some() {
+            if let (Some(value), Some(_)) = self.inner.next_pair() {
+                return Some(value);
+            }
+        }
+        None
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (left, right) = self.inner.lens();
+        (0, Some(min(left, right)))
+    }
+}
+
+impl<'a, T, C: Comparator<T>, A> FusedIterator for Intersection<'a, T, C, A> {}
+
+/// Lazy iterator over the values in one `BST` but not in another, obtained
+/// from `BST::difference`.
+pub struct Difference<'a, T, C, A = ()> {
+    inner: MergeIter<'a, T, C, A>,
+}
+
+impl<'a, T, C: Comparator<T>, A> Iterator for Difference<'a, T, C, A> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        while self.inner.left.peek().is_some() {
+            if let (Some(value), None) = self.inner.next_pair() {
+                return Some(value);
+            }
+        }
+        None
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (left, right) = self.inner.lens();
+        (left.saturating_sub(right), Some(left))
+    }
+}
+
+impl<'a, T, C: Comparator<T>, A> FusedIterator for Difference<'a, T, C, A> {}
+
+/// Lazy iterator over the values in exactly one of two `BST`s, obtained from
+/// `BST::symmetric_difference`.
+pub struct SymmetricDifference<'a, T, C, A = ()> {
+    inner: MergeIter<'a, T, C, A>,
+}
+
+impl<'a, T, C: Comparator<T>, A> Iterator for SymmetricDifference<'a, T, C, A> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            match self.inner.next_pair() {
+                (Some(value), None) | (None, Some(value)) => return Some(value),
+                (None, None) => return None,
+                (Some(_), Some(_)) => {}
+            }
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (left, right) = self.inner.lens();
+        (0, Some(left + right))
+    }
+}
+
+impl<'a, T, C: Comparator<T>, A> FusedIterator for SymmetricDifference<'a, T, C, A> {}
+
+/// Lazy set algebra. Both trees must be ordered the same way; the
+/// comparator of `self` is the one used.
+impl<T, C: Comparator<T>, A: Augment<T>> BST<T, C, A> {
+    /// Iterates in order over the values in `self` or `other`.
+    ///
+    /// Equal values are yielded once, taken from `self`.
+    pub fn union<'a>(&'a self, other: &'a BST<T, C, A>) -> Union<'a, T, C, A> {
+        Union {
+            inner: MergeIter::new(self, other),
+        }
+    }
+    /// Iterates in order over the values in both `self` and `other`.
+    pub fn intersection<'a>(&'a self, other: &'a BST<T, C, A>) -> Intersection<'a, T, C, A> {
+        Intersection {
+            inner: MergeIter::new(self, other),
+        }
+    }
+    /// Iterates in order over the values in `self` but not in `other`.
+    pub fn difference<'a>(&'a self, other: &'a BST<T, C, A>) -> Difference<'a, T, C, A> {
+        Difference {
+            inner: MergeIter::new(self, other),
+        }
+    }
+    /// Iterates in order over the values in exactly one of `self` and
+    /// `other`.
+    pub fn symmetric_difference<'a>(
+        &'a self,
+        other: &'a BST<T, C, A>,
+    ) -> SymmetricDifference<'a, T, C, A> {
+        SymmetricDifference {
+            inner: MergeIter::new(self, other),
+        }
+    }
+    /// Returns `true` if every value of `self` is in `other`.
+    pub fn is_subset(&self, other: &BST<T, C, A>) -> bool {
+        self.len() <= other.len() && self.difference(other).next().is_none()
+    }
+    /// Returns `true` if every value of `other` is in `self`.
+    pub fn is_superset(&self, other: &BST<T, C, A>) -> bool {
+        other.is_subset(self)
+    }
+    /// Returns `true` if `self` and `other` have no value in common.
+    pub fn is_disjoint(&self, other: &BST<T, C, A>) -> bool {
+        self.intersection(other).next().is_none()
+    }
+}
+
+/// Join-based bulk operations, taking O(m log(n / m + 1)) for trees of sizes
+/// m <= n. Both trees must be ordered by the same comparator.
+impl<K, V, A: Augment<K, V>> Node<K, V, A> {
+    /// Entries of both trees, keeping those of `tree` for keys in both.
+    pub(crate) fn union<C: Comparator<K>>(
+        tree: Link<K, V, A>,
+        other: Link<K, V, A>,
+        cmp: &C,
+    ) -> Link<K, V, A> {
+        match (tree, other) {
+            (None, other) => other,
+            (tree, None) => tree,
+            (Some(node), other) => {
+                let Node {
+                    key,
+                    value,
+                    left,
+                    right,
+                    ..
+                } = *node;
+                let (below, _, above) = Node::split(other, &key, cmp);
+                let left = Node::union(left, below, cmp);
+                let right = Node::union(right, above, cmp);
+                Node::join(left, key, value, right)
+            }
+        }
+    }
+    /// Entries of `tree` whose keys are also in `other`.
+    fn intersection<C: Comparator<K>>(
+        tree: Link<K, V, A>,
+        other: Link<K, V, A>,
+        cmp: &C,
+    ) -> Link<K, V, A> {
+        match (tree, other) {
+            (None, _) | (_, None) => None,
+            (Some(node), other) => {
+                let Node {
+                    key,
+                    value,
+                    left,
+                    right,
+                    ..
+                } = *node;
+                let (below, found, above) = Node::split(other, &key, cmp);
+                let left = Node::intersection(left, below, cmp);
+                let right = Node::intersection(right, above, cmp);
+                match found {
+                    Some(_) => Node::join(left, key, value, right),
+                    None => Node::concat(left, right),
+                }
+            }
+        }
+    }
+    /// Entries of `tree` whose keys are not in `other`.
+    fn difference<C: Comparator<K>>(
+        tree: Link<K, V, A>,
+        other: Link<K, V, A>,
+        cmp: &C,
+    ) -> Link<K, V, A> {
+        match (tree, other) {
+            (None, _) => None,
+            (tree, None) => tree,
+            (tree, Some(node)) => {
+                let Node {
+                    key, left, right, ..
+                } = *node;
+                let (below, _, above) = Node::split(tree, &key, cmp);
+                let left = Node::difference(below, left, cmp);
+                let right = Node::difference(above, right, cmp);
+                Node::concat(left, right)
+            }
+        }
+    }
+    /// Entries whose keys are in exactly one of the trees.
+    fn symmetric_difference<C: Comparator<K>>(
+        tree: Link<K, V, A>,
+        other: Link<K, V, A>,
+        cmp: &C,
+    ) -> Link<K, V, A> {
+        match (tree, other) {
+            (None, other) => other,
+            (tree, None) => tree,
+            (Some(node), other) => {
+                let Node {
+                    key,
+                    value,
+                    left,
+                    right,
+                    ..
+                } = *node;
+                let (below, found, above) = Node::split(other, &key, cmp);
+                let left = Node::symmetric_difference(left, below, cmp);
+                let right = Node::symmetric_difference(right, above, cmp);
+                match found {
+                    Some(_) => Node::concat(left, right),
+                    None => Node::join(left, key, value, right),
+                }
+            }
+        }
+    }
+}
+
+/// Owned union, built by joining so the result is balanced.
+impl<T, C: Comparator<T>, A: Augment<T>> BitOr for BST<T, C, A> {
+    type Output = BST<T, C, A>;
+    fn bitor(self, other: BST<T, C, A>) -> BST<T, C, A> {
+        BST {
+            root: Node::union(self.root, other.root, &self.comparator),
+            comparator: self.comparator,
+        }
+    }
+}
+
+/// Owned intersection, built by joining so the result is balanced.
+impl<T, C: Comparator<T>, A: Augment<T>> BitAnd for BST<T, C, A> {
+    type Output = BST<T, C, A>;
+    fn bitand(self, other: BST<T, C, A>) -> BST<T, C, A> {
+        BST {
+            root: Node::intersection(self.root, other.root, &self.comparator),
+            comparator: self.comparator,
+        }
+    }
+}
+
+/// Owned difference, built by joining so the result is balanced.
+impl<T, C: Comparator<T>, A: Augment<T>> Sub for BST<T, C, A> {
+    type Output = BST<T, C, A>;
+    fn sub(self, other: BST<T, C, A>) -> BST<T, C, A> {
+        BST {
+            root: Node::difference(self.root, other.root, &self.comparator),
+            comparator: self.comparator,
+        }
+    }
+}
+
+/// Owned symmetric difference, built by joining so the result is balanced.
+impl<T, C: Comparator<T>, A: Augment<T>> BitXor for BST<T, C, A> {
+    type Output = BST<T, C, A>;
+    fn bitxor(self, other: BST<T, C, A>) -> BST<T, C, A> {
+        BST {
+            root: Node::symmetric_difference(self.root, other.root, &self.comparator),
+            comparator: self.comparator,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::assert_balanced;
+
+    fn bst(values: impl Iterator<Item = i32>) -> BST<i32> {
+        let mut bst = BST::new();
+        for value in values {
+            bst.insert(value);
+        }
+        bst
+    }
+
+    fn collect<'a>(values: impl Iterator<Item = &'a i32>) -> Vec<i32> {
+        values.copied().collect()
+    }
+
+    fn filtered(test: impl Fn(&i32) -> bool) -> Vec<i32> {
+        (0..300).filter(test).collect()
+    }
+
+    #[test]
+    fn lazy() {
+        let twos = bst((0..300).step_by(2));
+        let threes = bst((0..300).step_by(3));
+        let empty = BST::new();
+
+        assert_eq!(
+            collect(twos.union(&threes)),
+            filtered(|v| v % 2 == 0 || v % 3 == 0)
+        );
+        assert_eq!(
+            collect(twos.intersection(&threes)),
+            filtered(|v| v % 6 == 0)
+        );
+        assert_eq!(
+            collect(twos.difference(&threes)),
+            filtered(|v| v % 2 == 0 && v % 3 != 0)
+        );
+        assert_eq!(
+            collect(twos.symmetric_difference(&threes)),
+            filtered(|v| (v % 2 == 0) != (v % 3 == 0))
+        );
+        assert_eq!(collect(twos.union(&empty)), collect(twos.iter()));
+        assert_eq!(twos.intersection(&empty).next(), None);
+        assert_eq!(empty.difference(&twos).next(), None);
+        assert_eq!(twos.union(&threes).size_hint(), (150, Some(250)));
+    }
+
+    #[test]
+    fn owned() {
+        let twos = || bst((0..300).step_by(2));
+        let threes = || bst((0..300).step_by(3));
+        let cases = [
+            (twos() | threes(), filtered(|v| v % 2 == 0 || v % 3 == 0)),
+            (twos() & threes(), filtered(|v| v % 6 == 0)),
+            (twos() - threes(), filtered(|v| v % 2 == 0 && v % 3 != 0)),
+            (threes() - twos(), filtered(|v| v % 3 == 0 && v % 2 != 0)),
+            (
+                twos() ^ threes(),
+                filtered(|v| (v % 2 == 0) != (v % 3 == 0)),
+            ),
+            (
+                bst(0..5) | bst(200..1000),
+                (0..5).chain(200..1000).collect(),
+            ),
+            (bst(0..1000) & bst(500..503), (500..503).collect()),
+            (bst(0..10) - BST::new(), (0..10).collect()),
+            (BST::new() ^ bst(0..10), (0..10).collect()),
+        ];
+        for (result, expected) in cases {
+            assert_balanced(&result.root);
+            assert_eq!(result.len(), expected.len());
+            assert_eq!(collect(result.iter()), expected);
+        }
+    }
+
+    #[test]
+    fn relations() {
+        let small = bst([4, 8].into_iter());
+        let large = bst((0..10).step_by(2));
+        let odd = bst((1..10).step_by(2));
+        assert!(small.is_subset(&large));
+        assert!(!large.is_subset(&small));
+        assert!(large.is_superset(&small));
+        assert!(!small.is_superset(&large));
+        assert!(!odd.is_superset(&small));
+        assert!(odd.is_disjoint(&large));
+        assert!(!small.is_disjoint(&large));
+        assert!(BST::new().is_subset(&odd));
+        assert!(odd.is_superset(&BST::new()));
+    }
+}
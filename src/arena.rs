@@ -0,0 +1,1394 @@
+use std::borrow::Borrow;
+use std::cmp::{max, Ordering};
+use std::fmt;
+use std::iter::FusedIterator;
+use std::mem;
+use std::ops::{Bound, RangeBounds};
+
+use crate::{check_range, Comparator, InOrder, OrdComparator, Shape, Subtree, MAX_DEPTH};
+
+/// Index standing in for a missing child.
+const NIL: u32 = u32::MAX;
+
+/// A node stored in the arena, linked to its children by slot index.
+///
+/// Heights and balance factors follow the conventions of the crate's
+/// `Node`: a leaf has height 0 and balance is right minus left.
+struct Node<T> {
+    value: T,
+    left: u32,
+    right: u32,
+    shape: Shape,
+}
+
+enum Slot<T> {
+    Occupied(Node<T>),
+    /// A released slot, linked to the next free one.
+    Vacant(u32),
+}
+
+/// The nodes passed by a descent from the root, the deepest last.
+///
+/// An AVL tree of fewer than `u32::MAX` nodes is less than 48 levels deep,
+/// so the path fits a fixed array and updates allocate nothing.
+struct Path {
+    nodes: [u32; MAX_DEPTH],
+    len: usize,
+}
+
+impl Path {
+    fn new() -> Path {
+        Path {
+            nodes: [NIL; MAX_DEPTH],
+            len: 0,
+        }
+    }
+    fn push(&mut self, index: u32) {
+        self.nodes[self.len] = index;
+        self.len += 1;
+    }
+    fn pop(&mut self) -> Option<u32> {
+        let index = self.last()?;
+        self.len -= 1;
+        Some(index)
+    }
+    fn clear(&mut self) {
+        self.len = 0;
+    }
+    fn last(&self) -> Option<u32> {
+        self.as_slice().last().copied()
+    }
+    fn as_slice(&self) -> &[u32] {
+        &self.nodes[..self.len]
+    }
+}
+
+/// The node storage of an `ArenaBST`.
+///
+/// Released slots are chained into a free list and reused by later inserts,
+/// so a tree that has reached its working size stops allocating.
+struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    /// First free slot, or `NIL`.
+    free: u32,
+}
+
+impl<T> Arena<T> {
+    fn with_capacity(capacity: usize) -> Arena<T> {
+        Arena {
+            slots: Vec::with_capacity(capacity),
+            free: NIL,
+        }
+    }
+    fn node(&self, index: u32) -> &Node<T> {
+        match &self.slots[index as usize] {
+            Slot::Occupied(node) => node,
+            Slot::Vacant(_) => panic!("index should point to a node"),
+        }
+    }
+    fn node_mut(&mut self, index: u32) -> &mut Node<T> {
+        match &mut self.slots[index as usize] {
+            Slot::Occupied(node) => node,
+            Slot::Vacant(_) => panic!("index should point to a node"),
+        }
+    }
+    fn alloc(&mut self, value: T) -> u32 {
+        let node = Slot::Occupied(Node {
+            value,
+            left: NIL,
+            right: NIL,
+            shape: Shape::new(0, 1),
+        });
+        if self.free != NIL {
+            let index = self.free;
+            match mem::replace(&mut self.slots[index as usize], node) {
+                Slot::Vacant(next) => self.free = next,
+                Slot::Occupied(_) => panic!("free list should only hold vacant slots"),
+            }
+            index
+        } else {
+            let index = self.slots.len();
+            assert!(index < NIL as usize, "arena is full");
+            self.slots.push(node);
+            index as u32
+        }
+    }
+    fn release(&mut self, index: u32) -> T {
+        let slot = mem::replace(&mut self.slots[index as usize], Slot::Vacant(self.free));
+        self.free = index;
+        match slot {
+            Slot::Occupied(node) => node.value,
+            Slot::Vacant(_) => panic!("index should point to a node"),
+        }
+    }
+    /// Height of the tree rooted at `index`, counting an empty tree as 0
+    /// and a leaf as 1.
+    fn height_of(&self, index: u32) -> u32 {
+        if index == NIL {
+            0
+        } else {
+            self.node(index).shape.height() + 1
+        }
+    }
+    fn size_of(&self, index: u32) -> usize {
+        if index == NIL {
+            0
+        } else {
+            self.node(index).shape.size()
+        }
+    }
+    fn update(&mut self, index: u32) {
+        let node = self.node(index);
+        let (left, right) = (node.left, node.right);
+        let height = max(self.height_of(left), self.height_of(right));
+        let size = 1 + self.size_of(left) + self.size_of(right);
+        self.node_mut(index).shape = Shape::new(height, size);
+    }
+    /// Height of the right subtree minus that of the left one.
+    fn balance_factor(&self, index: u32) -> i32 {
+        let node = self.node(index);
+        self.height_of(node.right) as i32 - self.height_of(node.left) as i32
+    }
+    fn rotate_right(&mut self, index: u32) -> u32 {
+        let pivot = self.node(index).left;
+        self.node_mut(index).left = self.node(pivot).right;
+        self.node_mut(pivot).right = index;
+        self.update(index);
+        self.update(pivot);
+        pivot
+    }
+    fn rotate_left(&mut self, index: u32) -> u32 {
+        let pivot = self.node(index).right;
+        self.node_mut(index).right = self.node(pivot).left;
+        self.node_mut(pivot).left = index;
+        self.update(index);
+        self.update(pivot);
+        pivot
+    }
+    /// Updates the node at `index` after one of its subtrees changed height by
+    /// at most one, rotating it back into balance. Returns the new subtree root.
+    fn rebalance(&mut self, index: u32) -> u32 {
+        self.update(index);
+        match self.balance_factor(index) {
+            -2 => {
+                let left = self.node(index).left;
+                if self.balance_factor(left) > 0 {
+                    self.node_mut(index).left = self.rotate_left(left);
+                }
+                self.rotate_right(index)
+            }
+            2 => {
+                let right = self.node(index).right;
+                if self.balance_factor(right) < 0 {
+                    self.node_mut(index).right = self.rotate_right(right);
+                }
+                self.rotate_left(index)
+            }
+            -1..=1 => index,
+            invalid_bf => panic!(
+                "Balance factor should be from interval [-2, 2], but is {}",
+                invalid_bf
+            ),
+        }
+    }
+    fn child(&self, index: u32, side: Ordering) -> u32 {
+        let node = self.node(index);
+        if side == Ordering::Less {
+            node.left
+        } else {
+            node.right
+        }
+    }
+    fn set_child(&mut self, index: u32, side: Ordering, child: u32) {
+        let node = self.node_mut(index);
+        if side == Ordering::Less {
+            node.left = child;
+        } else {
+            node.right = child;
+        }
+    }
+    /// Points the link from `parent` to its child `old` at `new` instead.
+    fn relink(&mut self, parent: u32, old: u32, new: u32) {
+        let node = self.node_mut(parent);
+        if node.left == old {
+            node.left = new;
+        } else {
+            node.right = new;
+        }
+    }
+    /// Updates the nodes of `path` from the bottom up after the subtree below
+    /// its last node changed, and returns the new root.
+    ///
+    /// Like `Node::retrace`, rotations and balance checks stop once a
+    /// subtree's height is unchanged, while sizes are updated up to the root.
+    fn retrace(&mut self, path: &[u32], mut height_changed: bool) -> u32 {
+        let (mut child, mut subtree) = (NIL, NIL);
+        for &index in path.iter().rev() {
+            if subtree != child {
+                self.relink(index, child, subtree);
+            }
+            child = index;
+            subtree = if height_changed {
+                let height = self.node(index).shape.height();
+                let subtree = self.rebalance(index);
+                height_changed = self.node(subtree).shape.height() != height;
+                subtree
+            } else {
+                self.update(index);
+                index
+            };
+        }
+        subtree
+    }
+    /// Walks down from `root` toward `value`, pushing the nodes passed onto
+    /// `path`.
+    ///
+    /// Returns the index of the node holding `value`, or the last step taken
+    /// from the end of `path` to the empty link where `value` belongs.
+    fn search<Q: ?Sized, C: Comparator<Q>>(
+        &self,
+        root: u32,
+        value: &Q,
+        cmp: &C,
+        path: &mut Path,
+    ) -> Result<u32, Ordering>
+    where
+        T: Borrow<Q>,
+    {
+        let mut index = root;
+        let mut step = Ordering::Equal;
+        while index != NIL {
+            step = cmp.compare(value, self.node(index).value.borrow());
+            if step == Ordering::Equal {
+                return Ok(index);
+            }
+            path.push(index);
+            index = self.child(index, step);
+        }
+        Err(step)
+    }
+    /// Walks down from `root` to the node at position `position` in key
+    /// order, pushing the nodes passed onto `path`. Returns `NIL` if there
+    /// are not that many nodes.
+    fn seek(&self, root: u32, mut position: usize, path: &mut Path) -> u32 {
+        let mut index = root;
+        while index != NIL {
+            let left = self.size_of(self.node(index).left);
+            let step = position.cmp(&left);
+            match step {
+                Ordering::Less => {}
+                Ordering::Equal => return index,
+                Ordering::Greater => position -= left + 1,
+            }
+            path.push(index);
+            index = self.child(index, step);
+        }
+        NIL
+    }
+    /// Walks down the `side` spine from the non-empty `root`, pushing the
+    /// nodes passed onto `path`, and returns the node at its end.
+    fn seek_outermost(&self, mut index: u32, side: Ordering, path: &mut Path) -> u32 {
+        loop {
+            let child = self.child(index, side);
+            if child == NIL {
+                return index;
+            }
+            path.push(index);
+            index = child;
+        }
+    }
+    /// Returns the position in key order of the empty link that `step`
+    /// leads to from the end of `path`.
+    fn position(&self, path: &Path, step: Ordering) -> usize {
+        let nodes = path.as_slice();
+        let mut position = 0;
+        for (depth, &index) in nodes.iter().enumerate() {
+            let node = self.node(index);
+            let turns_right = match nodes.get(depth + 1) {
+                Some(&child) => node.right == child,
+                None => step == Ordering::Greater,
+            };
+            if turns_right {
+                position += self.size_of(node.left) + 1;
+            }
+        }
+        position
+    }
+    /// Links a new node for `value` at the empty `step` child of the last
+    /// node of `path`. Returns the new root and the index of the new node.
+    fn insert_at(&mut self, path: &Path, step: Ordering, value: T) -> (u32, u32) {
+        let index = self.alloc(value);
+        match path.last() {
+            None => (index, index),
+            Some(parent) => {
+                self.set_child(parent, step, index);
+                (self.retrace(path.as_slice(), true), index)
+            }
+        }
+    }
+    /// Unlinks the node at `index`, which `path` leads to, without releasing
+    /// its slot, and returns the new root.
+    fn unlink(&mut self, path: &mut Path, index: u32) -> u32 {
+        let depth = path.len;
+        let node = self.node(index);
+        let (left, right) = (node.left, node.right);
+        let replacement = if left == NIL || right == NIL {
+            if left == NIL {
+                right
+            } else {
+                left
+            }
+        } else {
+            // The in-order successor is unlinked from its parent and takes
+            // the place of the removed node.
+            path.push(index);
+            let successor = self.seek_outermost(right, Ordering::Less, path);
+            let parent = path.last().expect("path holds the removed node");
+            let successor_right = self.node(successor).right;
+            self.relink(parent, successor, successor_right);
+            let removed = self.node(index);
+            let (left, right, shape) = (removed.left, removed.right, removed.shape);
+            let node = self.node_mut(successor);
+            node.left = left;
+            node.right = right;
+            node.shape = shape;
+            path.nodes[depth] = successor;
+            successor
+        };
+        if depth > 0 {
+            self.relink(path.nodes[depth - 1], index, replacement);
+        }
+        if path.len == 0 {
+            replacement
+        } else {
+            self.retrace(path.as_slice(), true)
+        }
+    }
+    /// Returns the value at the end of the `side` spine below `index`.
+    fn outermost(&self, mut index: u32, side: Ordering) -> Option<&T> {
+        let mut found = None;
+        while index != NIL {
+            found = Some(&self.node(index).value);
+            index = self.child(index, side);
+        }
+        found
+    }
+    /// Joins `left`, the unlinked node `pivot` and `right` into one balanced
+    /// tree, like `Node::join`, and returns its root.
+    ///
+    /// All values of `left` must be smaller than that of `pivot` and all
+    /// values of `right` greater.
+    fn join(&mut self, left: u32, pivot: u32, right: u32) -> u32 {
+        let (left_height, right_height) = (self.height_of(left), self.height_of(right));
+        if left_height > right_height + 1 {
+            self.join_along(left, Ordering::Greater, pivot, right)
+        } else if right_height > left_height + 1 {
+            self.join_along(right, Ordering::Less, pivot, left)
+        } else {
+            let node = self.node_mut(pivot);
+            node.left = left;
+            node.right = right;
+            self.update(pivot);
+            pivot
+        }
+    }
+    /// Joins `pivot` and the shorter `other` into the `side` spine of the
+    /// taller `tree`, `other` lying on that side of it.
+    fn join_along(&mut self, tree: u32, side: Ordering, pivot: u32, other: u32) -> u32 {
+        let inner = self.child(tree, side);
+        let joined = if self.height_of(inner) > self.height_of(other) + 1 {
+            self.join_along(inner, side, pivot, other)
+        } else if side == Ordering::Greater {
+            self.join(inner, pivot, other)
+        } else {
+            self.join(other, pivot, inner)
+        };
+        self.set_child(tree, side, joined);
+        self.rebalance(tree)
+    }
+    /// Concatenates two trees, all values of `left` being smaller than all
+    /// values of `right`.
+    fn concat(&mut self, left: u32, right: u32) -> u32 {
+        if left == NIL {
+            return right;
+        }
+        let mut path = Path::new();
+        let last = self.seek_outermost(left, Ordering::Greater, &mut path);
+        let left = self.unlink(&mut path, last);
+        self.join(left, last, right)
+    }
+    /// Splits the tree rooted at `index` into the values below a key, the
+    /// unlinked node equal to it (or `NIL`) and the values above it.
+    ///
+    /// `locate` compares the key with the value of a node, so the key may be
+    /// stored in the arena itself.
+    fn split<F: Fn(&Arena<T>, u32) -> Ordering>(
+        &mut self,
+        index: u32,
+        locate: &F,
+    ) -> (u32, u32, u32) {
+        if index == NIL {
+            return (NIL, NIL, NIL);
+        }
+        let (left, right) = (self.node(index).left, self.node(index).right);
+        match locate(self, index) {
+            Ordering::Equal => (left, index, right),
+            Ordering::Less => {
+                let (below, found, above) = self.split(left, locate);
+                (below, found, self.join(above, index, right))
+            }
+            Ordering::Greater => {
+                let (below, found, above) = self.split(right, locate);
+                (self.join(left, index, below), found, above)
+            }
+        }
+    }
+    /// Merges the trees rooted at `tree` and `other`, like `Node::union`,
+    /// keeping the values of `tree` for values in both.
+    fn union<C: Comparator<T>>(&mut self, tree: u32, other: u32, cmp: &C) -> u32 {
+        if tree == NIL {
+            return other;
+        }
+        if other == NIL {
+            return tree;
+        }
+        let (left, right) = (self.node(tree).left, self.node(tree).right);
+        let (below, found, above) = self.split(other, &|arena: &Arena<T>, index| {
+            cmp.compare(&arena.node(tree).value, &arena.node(index).value)
+        });
+        if found != NIL {
+            self.release(found);
+        }
+        let left = self.union(left, below, cmp);
+        let right = self.union(right, above, cmp);
+        self.join(left, tree, right)
+    }
+    /// Merges the trees rooted at `tree` and `other`, keeping the values of
+    /// `other` for values in both, like `Node::append`.
+    fn append<C: Comparator<T>>(&mut self, tree: u32, other: u32, cmp: &C) -> u32 {
+        let precedes = |left: u32, right: u32| match (
+            self.outermost(left, Ordering::Greater),
+            self.outermost(right, Ordering::Less),
+        ) {
+            (Some(last), Some(first)) => cmp.compare(last, first) == Ordering::Less,
+            _ => true,
+        };
+        if precedes(tree, other) {
+            self.concat(tree, other)
+        } else if precedes(other, tree) {
+            self.concat(other, tree)
+        } else {
+            self.union(other, tree, cmp)
+        }
+    }
+    /// Keeps the values for which `f` returns `true`, joining the kept parts
+    /// of each subtree back together, and returns the new root.
+    fn retain<F: FnMut(&T) -> bool>(&mut self, index: u32, f: &mut F) -> u32 {
+        if index == NIL {
+            return NIL;
+        }
+        let (left, right) = (self.node(index).left, self.node(index).right);
+        let left = self.retain(left, f);
+        let keep = f(&self.node(index).value);
+        let right = self.retain(right, f);
+        if keep {
+            self.join(left, index, right)
+        } else {
+            self.release(index);
+            self.concat(left, right)
+        }
+    }
+    /// Releases the nodes of the tree rooted at `index`, pushing their
+    /// values onto `values` in order.
+    fn drain(&mut self, index: u32, values: &mut Vec<T>) {
+        if index != NIL {
+            let (left, right) = (self.node(index).left, self.node(index).right);
+            self.drain(left, values);
+            values.push(self.release(index));
+            self.drain(right, values);
+        }
+    }
+    /// Builds a balanced tree from the next `len` values, which must be
+    /// increasing, and returns its root.
+    fn build(&mut self, values: &mut impl Iterator<Item = T>, len: usize) -> u32 {
+        if len == 0 {
+            return NIL;
+        }
+        let left = self.build(values, len / 2);
+        let value = values.next().expect("fewer values than expected");
+        let index = self.alloc(value);
+        let right = self.build(values, len - len / 2 - 1);
+        self.join(left, index, right)
+    }
+    fn find<Q: ?Sized, C: Comparator<Q>>(&self, mut index: u32, value: &Q, cmp: &C) -> Option<&T>
+    where
+        T: Borrow<Q>,
+    {
+        while index != NIL {
+            let node = self.node(index);
+            index = match cmp.compare(value, node.value.borrow()) {
+                Ordering::Less => node.left,
+                Ordering::Equal => return Some(&node.value),
+                Ordering::Greater => node.right,
+            };
+        }
+        None
+    }
+}
+
+/// A node of an arena, as walked by `InOrder`.
+struct Handle<'a, T> {
+    arena: &'a Arena<T>,
+    index: u32,
+}
+
+impl<T> Clone for Handle<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<'_, T> {}
+
+impl<'a, T> Handle<'a, T> {
+    /// Returns the handle of the node at `index`, or `None` for `NIL`.
+    fn new(arena: &'a Arena<T>, index: u32) -> Option<Handle<'a, T>> {
+        (index != NIL).then_some(Handle { arena, index })
+    }
+}
+
+impl<'a, T> Subtree for Handle<'a, T> {
+    type Item = &'a T;
+    type Key = T;
+    fn item(self) -> &'a T {
+        &self.arena.node(self.index).value
+    }
+    fn key(&self) -> &T {
+        self.item()
+    }
+    fn left(self) -> Option<Handle<'a, T>> {
+        Handle::new(self.arena, self.arena.node(self.index).left)
+    }
+    fn right(self) -> Option<Handle<'a, T>> {
+        Handle::new(self.arena, self.arena.node(self.index).right)
+    }
+    fn size(self) -> usize {
+        self.arena.size_of(self.index)
+    }
+}
+
+impl<'a, T, C> IntoIterator for &'a ArenaBST<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// In-order iterator over the values of an `ArenaBST`.
+pub struct Iter<'a, T> {
+    inner: InOrder<Handle<'a, T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> FusedIterator for Iter<'a, T> {}
+
+/// In-order iterator over the values of an `ArenaBST` within a range.
+pub struct Range<'a, T> {
+    inner: InOrder<Handle<'a, T>>,
+}
+
+impl<'a, T> Iterator for Range<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Range<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Range<'a, T> {}
+
+impl<'a, T> FusedIterator for Range<'a, T> {}
+
+/// Lazy iterator removing the values of an `ArenaBST` that match a
+/// predicate, obtained from `ArenaBST::extract_if`.
+///
+/// Values not yet reached when the iterator is dropped stay in the tree.
+pub struct ExtractIf<'a, T, F> {
+    arena: &'a mut Arena<T>,
+    root: &'a mut u32,
+    /// The ancestors of the next value to test.
+    path: Path,
+    /// The node of the next value to test, or `NIL` after the last one.
+    index: u32,
+    /// Position of the next value to test.
+    position: usize,
+    pred: F,
+}
+
+impl<T, F> ExtractIf<'_, T, F> {
+    /// Moves from a kept value to the next one.
+    fn advance(&mut self) {
+        self.position += 1;
+        let right = self.arena.node(self.index).right;
+        if right != NIL {
+            self.path.push(self.index);
+            self.index = self
+                .arena
+                .seek_outermost(right, Ordering::Less, &mut self.path);
+            return;
+        }
+        let mut child = self.index;
+        self.index = NIL;
+        while let Some(parent) = self.path.pop() {
+            if self.arena.node(parent).left == child {
+                self.index = parent;
+                return;
+            }
+            child = parent;
+        }
+    }
+}
+
+impl<'a, T, F: FnMut(&T) -> bool> Iterator for ExtractIf<'a, T, F> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        while self.index != NIL {
+            if (self.pred)(&self.arena.node(self.index).value) {
+                *self.root = self.arena.unlink(&mut self.path, self.index);
+                let value = self.arena.release(self.index);
+                // Removing a value leaves the position of the next one
+                // unchanged, so the walk finds its place again by position.
+                self.path.clear();
+                self.index = self.arena.seek(*self.root, self.position, &mut self.path);
+                return Some(value);
+            }
+            self.advance();
+        }
+        None
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.arena.size_of(*self.root) - self.position))
+    }
+}
+
+impl<'a, T, F: FnMut(&T) -> bool> FusedIterator for ExtractIf<'a, T, F> {}
+
+/// An ordered set with the same AVL balancing as `BST`, but whose nodes live
+/// in one contiguous `Vec` and link to each other by `u32` index.
+///
+/// Nodes sit close together in memory, and removed ones are recycled through
+/// a free list instead of being deallocated. Which backend is faster
+/// depends on the workload and the machine; `cargo bench --bench backends`
+/// compares the two. A tree holds fewer than `u32::MAX` values.
+///
+/// It has the set API of `BST` apart from cursors, summaries, the set
+/// algebra, `split`/`join` and `from_sorted_iter`. Values cannot move
+/// between arenas without being copied, so `split_off` and `append` take
+/// time linear in the values they move.
+pub struct ArenaBST<T, C = OrdComparator> {
+    arena: Arena<T>,
+    root: u32,
+    comparator: C,
+}
+
+impl<T: fmt::Debug, C> fmt::Debug for ArenaBST<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+/// Trees are equal when they hold equal values, whatever their shape.
+impl<T: PartialEq, C> PartialEq for ArenaBST<T, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq, C> Eq for ArenaBST<T, C> {}
+
+impl<T, C: Comparator<T> + Default> Default for ArenaBST<T, C> {
+    fn default() -> Self {
+        ArenaBST::with_comparator(C::default())
+    }
+}
+
+impl<T: Ord> ArenaBST<T> {
+    pub fn new() -> ArenaBST<T> {
+        ArenaBST::with_comparator(OrdComparator)
+    }
+    /// Creates an empty tree with room for `capacity` values.
+    pub fn with_capacity(capacity: usize) -> ArenaBST<T> {
+        ArenaBST::with_capacity_and_comparator(capacity, OrdComparator)
+    }
+}
+
+impl<T, C: Comparator<T> + Default> FromIterator<T> for ArenaBST<T, C> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut bst = ArenaBST::default();
+        bst.extend(iter);
+        bst
+    }
+}
+
+impl<T, C: Comparator<T>> Extend<T> for ArenaBST<T, C> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<T, C: Comparator<T>> ArenaBST<T, C> {
+    /// Creates an empty tree ordered by `comparator` instead of `Ord`.
+    pub fn with_comparator(comparator: C) -> ArenaBST<T, C> {
+        ArenaBST::with_capacity_and_comparator(0, comparator)
+    }
+    /// Creates an empty tree ordered by `comparator` with room for
+    /// `capacity` values.
+    pub fn with_capacity_and_comparator(capacity: usize, comparator: C) -> ArenaBST<T, C> {
+        ArenaBST {
+            arena: Arena::with_capacity(capacity),
+            root: NIL,
+            comparator,
+        }
+    }
+    pub fn comparator(&self) -> &C {
+        &self.comparator
+    }
+    /// Adds `value`, returning whether it was not present yet.
+    pub fn insert(&mut self, value: T) -> bool {
+        let mut path = Path::new();
+        match self
+            .arena
+            .search(self.root, &value, &self.comparator, &mut path)
+        {
+            Ok(_) => false,
+            Err(step) => {
+                self.root = self.arena.insert_at(&path, step, value).0;
+                true
+            }
+        }
+    }
+    /// Adds `value`, replacing and returning an equal value already present.
+    pub fn replace(&mut self, value: T) -> Option<T> {
+        let mut path = Path::new();
+        match self
+            .arena
+            .search(self.root, &value, &self.comparator, &mut path)
+        {
+            Ok(index) => Some(mem::replace(&mut self.arena.node_mut(index).value, value)),
+            Err(step) => {
+                self.root = self.arena.insert_at(&path, step, value).0;
+                None
+            }
+        }
+    }
+    pub fn contains<Q: ?Sized>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.get(value).is_some()
+    }
+    /// Returns the stored value equal to `value`.
+    pub fn get<Q: ?Sized>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.arena.find(self.root, value, &self.comparator)
+    }
+    /// Removes `value`, returning whether it was present.
+    pub fn remove<Q: ?Sized>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.take(value).is_some()
+    }
+    /// Removes and returns the stored value equal to `value`.
+    pub fn take<Q: ?Sized>(&mut self, value: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        let mut path = Path::new();
+        let index = self
+            .arena
+            .search(self.root, value, &self.comparator, &mut path)
+            .ok()?;
+        self.root = self.arena.unlink(&mut path, index);
+        Some(self.arena.release(index))
+    }
+    /// Moves all values `>= value` into a new tree.
+    ///
+    /// Splitting takes O(log n), after which the k values split off are
+    /// copied into the arena of the new tree in O(k).
+    pub fn split_off<Q: ?Sized>(&mut self, value: &Q) -> Self
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q> + Clone,
+    {
+        let cmp = &self.comparator;
+        let (below, found, above) = self.arena.split(self.root, &|arena: &Arena<T>, index| {
+            cmp.compare(value, arena.node(index).value.borrow())
+        });
+        self.root = below;
+        let mut values = Vec::with_capacity(self.arena.size_of(above) + 1);
+        if found != NIL {
+            values.push(self.arena.release(found));
+        }
+        self.arena.drain(above, &mut values);
+        let len = values.len();
+        let mut other = ArenaBST::with_capacity_and_comparator(len, self.comparator.clone());
+        other.root = other.arena.build(&mut values.into_iter(), len);
+        other
+    }
+    /// Moves all values of `other` into `self`, leaving `other` empty.
+    ///
+    /// Values of `other` replace equal ones of `self`. The m values of
+    /// `other` are copied into the arena of `self` in O(m). Merging them then
+    /// takes O(log n) when all values of one tree are smaller than all values
+    /// of the other, and O(m log(n / m + 1)) otherwise.
+    pub fn append(&mut self, other: &mut Self) {
+        let len = other.len();
+        let mut values = Vec::with_capacity(len);
+        other.arena.drain(other.root, &mut values);
+        other.clear();
+        let tree = self.arena.build(&mut values.into_iter(), len);
+        self.root = self.arena.append(self.root, tree, &self.comparator);
+    }
+    /// Keeps only the values for which `f` returns `true`, visiting them in
+    /// order.
+    ///
+    /// The tree is rebuilt by joining the kept values in a single O(n) pass.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.root = self.arena.retain(self.root, &mut f);
+    }
+    /// Lazily removes and yields, in order, the values for which `pred`
+    /// returns `true`.
+    ///
+    /// Each removal rebalances the tree in O(log n), while kept values take
+    /// O(1) amortized.
+    pub fn extract_if<F: FnMut(&T) -> bool>(&mut self, pred: F) -> ExtractIf<'_, T, F> {
+        let mut path = Path::new();
+        let index = self.arena.seek(self.root, 0, &mut path);
+        ExtractIf {
+            arena: &mut self.arena,
+            root: &mut self.root,
+            path,
+            index,
+            position: 0,
+            pred,
+        }
+    }
+    /// Returns the smallest value above `bound`.
+    ///
+    /// `Included(x)` finds the first value `>= x`, `Excluded(x)` the first
+    /// value `> x` and `Unbounded` the first value overall.
+    pub fn lower_bound<Q: ?Sized>(&self, bound: Bound<&Q>) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.handle()
+            .and_then(|root| root.first_in(&(bound, Bound::Unbounded), &self.comparator))
+            .map(Subtree::item)
+    }
+    /// Returns the greatest value below `bound`.
+    ///
+    /// `Included(x)` finds the last value `<= x`, `Excluded(x)` the last
+    /// value `< x` and `Unbounded` the last value overall.
+    pub fn upper_bound<Q: ?Sized>(&self, bound: Bound<&Q>) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.handle()
+            .and_then(|root| root.last_in(&(Bound::Unbounded, bound), &self.comparator))
+            .map(Subtree::item)
+    }
+    /// Returns the greatest value `<= value`.
+    pub fn floor<Q: ?Sized>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.upper_bound(Bound::Included(value))
+    }
+    /// Returns the smallest value `>= value`.
+    pub fn ceiling<Q: ?Sized>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.lower_bound(Bound::Included(value))
+    }
+    /// Returns the greatest value `< value`.
+    pub fn predecessor<Q: ?Sized>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.upper_bound(Bound::Excluded(value))
+    }
+    /// Returns the smallest value `> value`.
+    pub fn successor<Q: ?Sized>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.lower_bound(Bound::Excluded(value))
+    }
+    /// Returns the number of values smaller than `value`, which is also the
+    /// position `value` has, or would have, in iteration order.
+    pub fn rank<Q: ?Sized>(&self, value: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.handle().map_or(0, |root| {
+            root.count_below(
+                &(Bound::Included(value), Bound::Unbounded),
+                &self.comparator,
+            )
+        })
+    }
+    /// Returns the number of values within `range` in O(log n).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end, or if both
+    /// are equal and excluded.
+    pub fn count_in_range<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> usize
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        check_range(&range, &self.comparator);
+        self.handle().map_or(0, |root| {
+            root.size()
+                - root.count_below(&range, &self.comparator)
+                - root.count_above(&range, &self.comparator)
+        })
+    }
+    /// Iterates in order over the values within `range`.
+    ///
+    /// Takes O(log n) to find both ends of the range, plus O(1) amortized per
+    /// value returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end, or if both
+    /// are equal and excluded.
+    pub fn range<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> Range<'_, T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        Range {
+            inner: InOrder::range(self.handle(), &range, &self.comparator),
+        }
+    }
+    /// Gets the entry for `value` for in-place manipulation.
+    ///
+    /// The entry keeps the path of its single descent, and nodes keep their
+    /// slot through rotations, so it reaches the value without searching
+    /// again.
+    pub fn entry(&mut self, value: T) -> Entry<'_, T> {
+        let mut path = Path::new();
+        match self
+            .arena
+            .search(self.root, &value, &self.comparator, &mut path)
+        {
+            Ok(index) => Entry::Occupied(OccupiedEntry {
+                arena: &mut self.arena,
+                root: &mut self.root,
+                path,
+                index,
+            }),
+            Err(step) => Entry::Vacant(VacantEntry {
+                value,
+                arena: &mut self.arena,
+                root: &mut self.root,
+                path,
+                step,
+            }),
+        }
+    }
+}
+
+impl<T, C> ArenaBST<T, C> {
+    fn handle(&self) -> Option<Handle<'_, T>> {
+        Handle::new(&self.arena, self.root)
+    }
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            inner: InOrder::new(self.handle()),
+        }
+    }
+    /// Returns the number of values in the tree.
+    pub fn len(&self) -> usize {
+        self.arena.size_of(self.root)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.root == NIL
+    }
+    /// Returns the smallest value in the tree.
+    pub fn first(&self) -> Option<&T> {
+        self.arena.outermost(self.root, Ordering::Less)
+    }
+    /// Returns the greatest value in the tree.
+    pub fn last(&self) -> Option<&T> {
+        self.arena.outermost(self.root, Ordering::Greater)
+    }
+    /// Removes and returns the smallest value in the tree.
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.pop(Ordering::Less)
+    }
+    /// Removes and returns the greatest value in the tree.
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.pop(Ordering::Greater)
+    }
+    /// Removes the value at the end of the `side` spine.
+    fn pop(&mut self, side: Ordering) -> Option<T> {
+        if self.root == NIL {
+            return None;
+        }
+        let mut path = Path::new();
+        let index = self.arena.seek_outermost(self.root, side, &mut path);
+        self.root = self.arena.unlink(&mut path, index);
+        Some(self.arena.release(index))
+    }
+    /// Returns the value at position `index` in iteration order, in O(log n).
+    pub fn nth(&self, mut index: usize) -> Option<&T> {
+        let mut node = self.root;
+        while node != NIL {
+            let current = self.arena.node(node);
+            let left = self.arena.size_of(current.left);
+            node = match index.cmp(&left) {
+                Ordering::Less => current.left,
+                Ordering::Equal => return Some(&current.value),
+                Ordering::Greater => {
+                    index -= left + 1;
+                    current.right
+                }
+            };
+        }
+        None
+    }
+    /// Removes every value, keeping the allocated slots for reuse.
+    pub fn clear(&mut self) {
+        self.arena.slots.clear();
+        self.arena.free = NIL;
+        self.root = NIL;
+    }
+}
+
+/// A view into a single value of an `ArenaBST`, obtained from
+/// `ArenaBST::entry`.
+pub enum Entry<'a, T> {
+    Occupied(OccupiedEntry<'a, T>),
+    Vacant(VacantEntry<'a, T>),
+}
+
+/// An entry whose value is present in the tree.
+pub struct OccupiedEntry<'a, T> {
+    arena: &'a mut Arena<T>,
+    root: &'a mut u32,
+    /// The ancestors of the node.
+    path: Path,
+    index: u32,
+}
+
+/// An entry whose value is missing from the tree.
+pub struct VacantEntry<'a, T> {
+    value: T,
+    arena: &'a mut Arena<T>,
+    root: &'a mut u32,
+    /// The nodes down to the empty link where the value belongs.
+    path: Path,
+    /// The side of the last node of `path` that link is on.
+    step: Ordering,
+}
+
+impl<'a, T> Entry<'a, T> {
+    pub fn get(&self) -> &T {
+        match self {
+            Entry::Occupied(entry) => entry.get(),
+            Entry::Vacant(entry) => entry.get(),
+        }
+    }
+    /// Inserts the value if the entry is vacant, and returns a reference to
+    /// the value stored in the tree.
+    pub fn or_insert(self) -> &'a T {
+        match self {
+            Entry::Occupied(entry) => entry.into_ref(),
+            Entry::Vacant(entry) => entry.insert(),
+        }
+    }
+}
+
+impl<'a, T> OccupiedEntry<'a, T> {
+    pub fn get(&self) -> &T {
+        &self.arena.node(self.index).value
+    }
+    /// Converts the entry into a reference to the stored value.
+    pub fn into_ref(self) -> &'a T {
+        let arena: &'a Arena<T> = self.arena;
+        &arena.node(self.index).value
+    }
+    /// Removes the value from the tree and returns the stored one.
+    pub fn remove(mut self) -> T {
+        *self.root = self.arena.unlink(&mut self.path, self.index);
+        self.arena.release(self.index)
+    }
+}
+
+impl<'a, T> VacantEntry<'a, T> {
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+    pub fn into_value(self) -> T {
+        self.value
+    }
+    /// Inserts the value and returns a reference to it.
+    pub fn insert(self) -> &'a T {
+        let (root, index) = self.arena.insert_at(&self.path, self.step, self.value);
+        *self.root = root;
+        let arena: &'a Arena<T> = self.arena;
+        &arena.node(index).value
+    }
+    /// Inserts the value and returns an entry for it.
+    ///
+    /// Rebalancing may change the ancestors of the new node, so they are
+    /// found again by its position, in O(log n) without comparisons.
+    pub fn insert_entry(self) -> OccupiedEntry<'a, T> {
+        let position = self.arena.position(&self.path, self.step);
+        let (root, index) = self.arena.insert_at(&self.path, self.step, self.value);
+        *self.root = root;
+        let mut path = Path::new();
+        self.arena.seek(root, position, &mut path);
+        OccupiedEntry {
+            arena: self.arena,
+            root: self.root,
+            path,
+            index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BST;
+
+    fn assert_balanced<T>(arena: &Arena<T>, index: u32) {
+        if index == NIL {
+            return;
+        }
+        let node = arena.node(index);
+        assert_balanced(arena, node.left);
+        assert_balanced(arena, node.right);
+        assert!(arena.balance_factor(index).abs() <= 1);
+        assert_eq!(
+            node.shape.height(),
+            max(arena.height_of(node.left), arena.height_of(node.right))
+        );
+        assert_eq!(
+            node.shape.size(),
+            1 + arena.size_of(node.left) + arena.size_of(node.right)
+        );
+    }
+
+    #[test]
+    fn matches_box_backend() {
+        let mut arena = ArenaBST::new();
+        let mut boxed = BST::new();
+        for i in 0..2000u32 {
+            let value = (i * 7919) % 1009;
+            if i % 3 == 2 {
+                assert_eq!(arena.remove(&value), boxed.remove(&value));
+            } else {
+                assert_eq!(arena.insert(value), boxed.insert(value));
+            }
+        }
+        assert_balanced(&arena.arena, arena.root);
+        assert_eq!(arena.len(), boxed.len());
+        assert!(arena.iter().eq(boxed.iter()));
+        assert!(arena.iter().rev().eq(boxed.iter().rev()));
+        assert_eq!(arena.nth(17), boxed.nth(17));
+        assert_eq!((arena.first(), arena.last()), (boxed.first(), boxed.last()));
+    }
+
+    #[test]
+    fn queries_match_box_backend() {
+        let arena: ArenaBST<i32> = (0..300).map(|i| (i * 37) % 401).collect();
+        let boxed: BST<i32> = arena.iter().copied().collect();
+        for value in -2..410 {
+            assert_eq!(arena.floor(&value), boxed.floor(&value));
+            assert_eq!(arena.ceiling(&value), boxed.ceiling(&value));
+            assert_eq!(arena.predecessor(&value), boxed.predecessor(&value));
+            assert_eq!(arena.successor(&value), boxed.successor(&value));
+            assert_eq!(arena.rank(&value), boxed.rank(&value));
+        }
+        for (start, end) in [(0, 0), (-5, 3), (10, 200), (350, 500)] {
+            assert!(arena.range(start..end).eq(boxed.range(start..end)));
+            assert!(arena
+                .range(start..=end)
+                .rev()
+                .eq(boxed.range(start..=end).rev()));
+            assert_eq!(
+                arena.range(start..end).len(),
+                boxed.count_in_range(start..end)
+            );
+            assert_eq!(
+                arena.count_in_range((Bound::Excluded(start), Bound::Unbounded)),
+                boxed.count_in_range((Bound::Excluded(start), Bound::Unbounded))
+            );
+        }
+        assert_eq!(ArenaBST::<i32>::new().range(..).next(), None);
+    }
+
+    #[test]
+    fn updates_match_box_backend() {
+        let mut arena = ArenaBST::with_comparator(|a: &(i32, char), b: &(i32, char)| a.0.cmp(&b.0));
+        let mut boxed = BST::with_comparator(|a: &(i32, char), b: &(i32, char)| a.0.cmp(&b.0));
+        for i in 0..200 {
+            let value = ((i * 53) % 211, 'a');
+            assert_eq!(arena.insert(value), boxed.insert(value));
+        }
+        for i in 0..50 {
+            let value = ((i * 7) % 230, 'b');
+            assert_eq!(arena.replace(value), boxed.replace(value));
+        }
+        assert_eq!(arena.pop_first(), boxed.pop_first());
+        assert_eq!(arena.pop_last(), boxed.pop_last());
+        assert_balanced(&arena.arena, arena.root);
+
+        for key in [3, 5, 500, 77] {
+            match (arena.entry((key, 'c')), boxed.entry((key, 'c'))) {
+                (Entry::Occupied(entry), crate::Entry::Occupied(expected)) => {
+                    assert_eq!(entry.get(), expected.get());
+                    assert_eq!(entry.remove(), expected.remove());
+                }
+                (Entry::Vacant(entry), crate::Entry::Vacant(expected)) => {
+                    assert_eq!(entry.get(), expected.get());
+                    let entry = entry.insert_entry();
+                    assert_eq!(entry.get(), expected.insert_entry().get());
+                    assert_eq!(entry.into_ref(), &(key, 'c'));
+                }
+                _ => panic!("entries should agree on {key}"),
+            }
+        }
+        assert_eq!(
+            arena.entry((9, 'd')).or_insert(),
+            boxed.entry((9, 'd')).or_insert()
+        );
+        if let Entry::Vacant(entry) = arena.entry((600, 'e')) {
+            entry.insert_entry().remove();
+        }
+        assert_balanced(&arena.arena, arena.root);
+        assert!(arena.iter().eq(boxed.iter()));
+
+        arena.retain(|value| value.0 % 3 != 0);
+        boxed.retain(|value| value.0 % 3 != 0);
+        let first_two: Vec<_> = arena.extract_if(|value| value.0 % 4 == 0).take(2).collect();
+        assert_eq!(first_two.len(), 2);
+        let expected: Vec<_> = boxed.extract_if(|value| value.0 % 4 == 0).take(2).collect();
+        assert_eq!(first_two, expected);
+        assert!(arena.iter().eq(boxed.iter()));
+        assert!(arena
+            .extract_if(|value| value.0 % 5 == 0)
+            .eq(boxed.extract_if(|value| value.0 % 5 == 0)));
+        assert_balanced(&arena.arena, arena.root);
+        assert!(arena.iter().eq(boxed.iter()));
+    }
+
+    #[test]
+    fn split_off_and_append() {
+        let mut arena: ArenaBST<i32> = (0..100).collect();
+        let mut above = arena.split_off(&60);
+        assert!(arena.iter().copied().eq(0..60));
+        assert!(above.iter().copied().eq(60..100));
+        assert_balanced(&arena.arena, arena.root);
+        assert_balanced(&above.arena, above.root);
+        assert!(arena.split_off(&1000).is_empty());
+
+        arena.append(&mut above);
+        assert!(above.is_empty());
+        assert!(arena.iter().copied().eq(0..100));
+        assert_balanced(&arena.arena, arena.root);
+
+        let mut odd: ArenaBST<i32> = (0..150).filter(|value| value % 2 == 1).collect();
+        odd.append(&mut arena);
+        let expected: Vec<i32> = (0..100).chain((101..150).step_by(2)).collect();
+        assert!(odd.iter().copied().eq(expected));
+        assert_balanced(&odd.arena, odd.root);
+    }
+
+    #[test]
+    fn capacity_with_comparator() {
+        let mut bst = ArenaBST::with_capacity_and_comparator(8, |a: &i32, b: &i32| b.cmp(a));
+        assert!(bst.arena.slots.capacity() >= 8);
+        bst.extend([3, 1, 4, 1, 5]);
+        assert!(bst.iter().copied().eq([5, 4, 3, 1]));
+        assert_balanced(&bst.arena, bst.root);
+
+        let ascending: ArenaBST<i32> = (0..5).collect();
+        let descending: ArenaBST<i32> = (0..5).rev().collect();
+        let root_value = |bst: &ArenaBST<i32>| bst.arena.node(bst.root).value;
+        assert_ne!(root_value(&ascending), root_value(&descending));
+        assert_eq!(ascending, descending);
+    }
+
+    #[test]
+    fn free_slots_are_reused() {
+        let mut bst: ArenaBST<i32> = (0..100).collect();
+        for value in 0..50 {
+            assert_eq!(bst.take(&value), Some(value));
+        }
+        assert_balanced(&bst.arena, bst.root);
+        for value in 100..150 {
+            assert!(bst.insert(value));
+        }
+        assert_eq!(bst.arena.slots.len(), 100);
+        assert!(bst.iter().copied().eq(50..150));
+        assert_eq!(
+            format!("{:?}", ArenaBST::<i32>::from_iter([2, 1])),
+            "{1, 2}"
+        );
+
+        bst.clear();
+        assert!(bst.is_empty());
+        assert_eq!((bst.first(), bst.last()), (None, None));
+        assert_eq!(bst, ArenaBST::new());
+    }
+}
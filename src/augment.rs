@@ -0,0 +1,47 @@
+/// A summary each node keeps of the entries in its subtree.
+///
+/// Trees are not augmented by default, which is the `()` summary. With any
+/// other summary the tree can fold a whole key range in O(log n). Set
+/// summaries see the values, with `()` as the map value:
+///
+/// ```
+/// use bst::{Augment, OrdComparator, BST};
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Sum(i64);
+///
+/// impl Augment<i64> for Sum {
+///     fn summarize(key: &i64, _: &()) -> Sum {
+///         Sum(*key)
+///     }
+///     fn combine(&self, other: &Sum) -> Sum {
+///         Sum(self.0 + other.0)
+///     }
+/// }
+///
+/// let mut bst: BST<i64, OrdComparator, Sum> = BST::with_augment(OrdComparator);
+/// for value in 1..=10 {
+///     bst.insert(value);
+/// }
+/// assert_eq!(bst.fold_range(3..6), Some(Sum(12)));
+/// assert_eq!(bst.summary(), Some(&Sum(55)));
+/// ```
+///
+/// Map summaries see values as well as keys, so an augmented `BSTMap` only
+/// lets values change through methods that refresh the summaries above
+/// them, such as `insert` and `Entry::and_modify`. Mutable references to
+/// values are handed out by maps without a summary only.
+pub trait Augment<K, V = ()> {
+    /// Summarizes a single entry.
+    fn summarize(key: &K, value: &V) -> Self;
+    /// Combines the summaries of two adjacent runs of entries, `self` being
+    /// the summary of the smaller keys.
+    ///
+    /// Must be associative, but need not be commutative.
+    fn combine(&self, other: &Self) -> Self;
+}
+
+impl<K, V> Augment<K, V> for () {
+    fn summarize(_: &K, _: &V) {}
+    fn combine(&self, _: &()) {}
+}
@@ -0,0 +1,40 @@
+use std::cmp::Ordering;
+
+/// Decides the order of the elements in a `BST` or the keys in a `BSTMap`.
+///
+/// Besides `OrdComparator`, which the trees use by default, any
+/// `Fn(&T, &T) -> Ordering` closure is a comparator, so the order can be
+/// chosen at runtime:
+///
+/// ```
+/// use bst::BST;
+///
+/// let by_len = |a: &&str, b: &&str| a.len().cmp(&b.len()).then(a.cmp(b));
+/// let mut bst = BST::with_comparator(by_len);
+/// bst.insert("ccc");
+/// bst.insert("a");
+/// bst.insert("bb");
+/// assert_eq!(bst.iter().copied().collect::<Vec<_>>(), ["a", "bb", "ccc"]);
+/// ```
+///
+/// A comparator must be a total order and must not change while it is used
+/// by a tree, otherwise lookups may miss elements that are present.
+pub trait Comparator<T: ?Sized> {
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
+
+/// Compares with `Ord::cmp`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OrdComparator;
+
+impl<T: Ord + ?Sized> Comparator<T> for OrdComparator {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+impl<T: ?Sized, F: Fn(&T, &T) -> Ordering> Comparator<T> for F {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
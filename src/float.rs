@@ -0,0 +1,96 @@
+use std::cmp::Ordering;
+
+macro_rules! total_float {
+    ($(#[$doc:meta])* $name:ident, $float:ty) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, Default)]
+        pub struct $name(pub $float);
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.cmp(other) == Ordering::Equal
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl PartialOrd for $name {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $name {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.0.total_cmp(&other.0)
+            }
+        }
+
+        impl From<$float> for $name {
+            fn from(value: $float) -> Self {
+                $name(value)
+            }
+        }
+
+        impl From<$name> for $float {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
+    };
+}
+
+total_float!(
+    /// An `f64` with a total order, so that it can be stored in a `BST`.
+    ///
+    /// Values are compared with `f64::total_cmp`, i.e. the IEEE 754
+    /// `totalOrder` predicate:
+    /// `-NaN < -inf < ... < -0.0 < 0.0 < ... < inf < NaN`.
+    /// This differs from `f64`'s own `PartialOrd` in two ways: every NaN is
+    /// ordered (and equal only to a NaN with the same bit pattern) and
+    /// `-0.0` is a different key than `0.0`.
+    TotalF64,
+    f64
+);
+
+total_float!(
+    /// An `f32` with a total order, so that it can be stored in a `BST`.
+    ///
+    /// See `TotalF64` for the order used.
+    TotalF32,
+    f32
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BST;
+
+    #[test]
+    fn total_order() {
+        assert!(TotalF64(-f64::NAN) < TotalF64(f64::NEG_INFINITY));
+        assert!(TotalF64(-0.0) < TotalF64(0.0));
+        assert!(TotalF64(f64::INFINITY) < TotalF64(f64::NAN));
+        assert_eq!(TotalF64(f64::NAN), TotalF64(f64::NAN));
+        assert!(TotalF32(1.5) < TotalF32(f32::NAN));
+        assert_eq!(f32::from(TotalF32::from(2.5)), 2.5);
+    }
+
+    #[test]
+    fn nan_is_not_lost() {
+        let mut bst: BST<TotalF64> = BST::new();
+        for value in [1.0, f64::NAN, -3.5, 0.0, -0.0, f64::NAN] {
+            bst.insert(TotalF64(value));
+        }
+        assert!(bst.contains(&TotalF64(f64::NAN)));
+        let values: Vec<f64> = bst.iter().map(|value| value.0).collect();
+        assert_eq!(values.len(), 5);
+        assert_eq!(&values[..3], &[-3.5, -0.0, 0.0]);
+        assert!(values[1].is_sign_negative());
+        assert!(values[2].is_sign_positive());
+        assert!(values[4].is_nan());
+
+        assert!(bst.remove(&TotalF64(f64::NAN)));
+        assert!(!bst.contains(&TotalF64(f64::NAN)));
+    }
+}
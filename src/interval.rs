@@ -0,0 +1,332 @@
+use std::cmp::{max, Ordering};
+use std::iter::FusedIterator;
+use std::ops::{Bound, Range};
+
+use crate::{Augment, Comparator, Link, Node, NodeRefIter};
+
+/// Orders intervals by their start, then by their end.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct IntervalOrder;
+
+impl<K: Ord> Comparator<Range<K>> for IntervalOrder {
+    fn compare(&self, a: &Range<K>, b: &Range<K>) -> Ordering {
+        a.start.cmp(&b.start).then_with(|| a.end.cmp(&b.end))
+    }
+}
+
+/// The greatest end among the intervals of a subtree.
+#[derive(Debug, PartialEq)]
+struct MaxEnd<K>(K);
+
+impl<K: Ord + Clone, V> Augment<Range<K>, V> for MaxEnd<K> {
+    fn summarize(key: &Range<K>, _: &V) -> MaxEnd<K> {
+        MaxEnd(key.end.clone())
+    }
+    fn combine(&self, other: &MaxEnd<K>) -> MaxEnd<K> {
+        MaxEnd(max(&self.0, &other.0).clone())
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a IntervalTree<K, V> {
+    type Item = (&'a Range<K>, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the entries of an `IntervalTree`, ordered by start and
+/// then by end.
+pub struct Iter<'a, K, V> {
+    inner: NodeRefIter<'a, Range<K>, V, MaxEnd<K>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a Range<K>, &'a V);
+    fn next(&mut self) -> Option<(&'a Range<K>, &'a V)> {
+        self.inner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a Range<K>, &'a V)> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+
+/// Iterator over the entries of an `IntervalTree` that overlap a point or a
+/// range, in the same order as `Iter`.
+///
+/// Intervals starting within a queried range all overlap it, so after an
+/// O(log n) descent they come from a plain range iteration at O(1) amortized
+/// each. Intervals starting earlier overlap only if they end late enough. They
+/// are found by skipping subtrees in which every interval ends too early. That
+/// still walks through every ancestor of the intervals reported, so each of
+/// them costs O(log n) in the worst case, when they are scattered among
+/// intervals that end too early. A query reporting k intervals thus takes
+/// O(log n + k log(n / k + 1)). The greatest end per subtree cannot guarantee
+/// O(log n + k), which needs a priority search tree.
+pub struct Overlaps<'a, K, V> {
+    stack: Vec<&'a Node<Range<K>, V, MaxEnd<K>>>,
+    /// Intervals from the stack are reported if they end after `low`
+    low: K,
+    /// and start before `high`.
+    high: Bound<K>,
+    /// The intervals starting within the queried range, reported after
+    /// those from the stack.
+    within: Option<NodeRefIter<'a, Range<K>, V, MaxEnd<K>>>,
+}
+
+impl<'a, K: Ord, V> Overlaps<'a, K, V> {
+    fn new(
+        root: &'a Link<Range<K>, V, MaxEnd<K>>,
+        low: K,
+        high: Bound<K>,
+        within: Option<NodeRefIter<'a, Range<K>, V, MaxEnd<K>>>,
+    ) -> Overlaps<'a, K, V> {
+        let mut overlaps = Overlaps {
+            stack: Vec::new(),
+            low,
+            high,
+            within,
+        };
+        overlaps.push_left(root.as_deref());
+        overlaps
+    }
+    fn push_left(&mut self, mut node: Option<&'a Node<Range<K>, V, MaxEnd<K>>>) {
+        while let Some(current) = node {
+            if current.summary.0 <= self.low {
+                break;
+            }
+            self.stack.push(current);
+            node = current.left.as_deref();
+        }
+    }
+    fn starts_early_enough(&self, start: &K) -> bool {
+        match &self.high {
+            Bound::Included(high) => start <= high,
+            Bound::Excluded(high) => start < high,
+            Bound::Unbounded => true,
+        }
+    }
+}
+
+impl<'a, K: Ord, V> Iterator for Overlaps<'a, K, V> {
+    type Item = (&'a Range<K>, &'a V);
+    fn next(&mut self) -> Option<(&'a Range<K>, &'a V)> {
+        while let Some(node) = self.stack.pop() {
+            if !self.starts_early_enough(&node.key.start) {
+                self.stack.clear();
+                break;
+            }
+            self.push_left(node.right.as_deref());
+            if node.key.end > self.low {
+                return Some((&node.key, &node.value));
+            }
+        }
+        self.within.as_mut()?.next()
+    }
+}
+
+impl<'a, K: Ord, V> FusedIterator for Overlaps<'a, K, V> {}
+
+/// A map from half-open intervals `start..end` to values.
+///
+/// Intervals are kept in the AVL tree shared with `BST` and `BSTMap`, ordered
+/// by start and then by end, and every node tracks the greatest end in its
+/// subtree. Inserting an interval that is already present replaces its value.
+#[derive(Debug)]
+pub struct IntervalTree<K, V> {
+    root: Link<Range<K>, V, MaxEnd<K>>,
+}
+
+/// Trees are equal when they hold equal entries, whatever their shape.
+impl<K: PartialEq, V: PartialEq> PartialEq for IntervalTree<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, V: Eq> Eq for IntervalTree<K, V> {}
+
+impl<K: Ord + Clone, V> Default for IntervalTree<K, V> {
+    fn default() -> Self {
+        IntervalTree::new()
+    }
+}
+
+impl<K: Ord + Clone, V> IntervalTree<K, V> {
+    pub fn new() -> IntervalTree<K, V> {
+        IntervalTree { root: None }
+    }
+    /// Inserts `value` for `interval`, returning the value it replaced.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval is empty.
+    pub fn insert(&mut self, interval: Range<K>, value: V) -> Option<V> {
+        assert!(interval.start < interval.end, "interval must not be empty");
+        Node::insert_into(&mut self.root, interval, value, &IntervalOrder)
+    }
+    /// Returns the value stored for exactly `interval`.
+    pub fn get(&self, interval: &Range<K>) -> Option<&V> {
+        self.root
+            .as_ref()
+            .and_then(|root| root.find(interval, &IntervalOrder))
+            .map(|node| &node.value)
+    }
+    /// Removes exactly `interval`, returning its value.
+    pub fn remove(&mut self, interval: &Range<K>) -> Option<V> {
+        Node::delete_from(&mut self.root, interval, &IntervalOrder).map(|(_, value)| value)
+    }
+    /// Iterates over the intervals containing `point`.
+    ///
+    /// Reporting k intervals takes O(log n + k log(n / k + 1)) in the worst
+    /// case, not O(log n + k): see `Overlaps`.
+    pub fn stab(&self, point: &K) -> Overlaps<'_, K, V> {
+        Overlaps::new(
+            &self.root,
+            point.clone(),
+            Bound::Included(point.clone()),
+            None,
+        )
+    }
+    /// Iterates over the intervals sharing at least one point with `range`.
+    ///
+    /// An empty `range` overlaps nothing. Reporting k intervals takes
+    /// O(log n + k log(n / k + 1)) in the worst case, of which the intervals
+    /// starting within `range` cost only O(1) each: see `Overlaps`.
+    pub fn overlapping(&self, range: Range<K>) -> Overlaps<'_, K, V> {
+        if range.start >= range.end {
+            return Overlaps::new(&None, range.start, Bound::Unbounded, None);
+        }
+        // The empty intervals at either end of `range` sort before every
+        // interval starting at the same point.
+        let Range { start, end } = range;
+        let starts = (
+            Bound::Included(start.clone()..start.clone()),
+            Bound::Excluded(end.clone()..end),
+        );
+        let within = NodeRefIter::range(self.root.as_deref(), &starts, &IntervalOrder);
+        Overlaps::new(
+            &self.root,
+            start.clone(),
+            Bound::Excluded(start),
+            Some(within),
+        )
+    }
+}
+
+impl<K, V> IntervalTree<K, V> {
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: NodeRefIter::new(self.root.as_deref()),
+        }
+    }
+    /// Returns the number of intervals in the tree.
+    pub fn len(&self) -> usize {
+        Node::size_of(&self.root)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stab_and_overlap() {
+        let mut tree = IntervalTree::new();
+        assert_eq!(tree.stab(&0).next(), None);
+        tree.insert(9..17, "day");
+        tree.insert(12..13, "lunch");
+        tree.insert(0..24, "all");
+        tree.insert(17..22, "evening");
+        assert_eq!(tree.insert(12..13, "break"), Some("lunch"));
+        assert_eq!(tree.len(), 4);
+
+        fn stab<'a>(tree: &IntervalTree<i32, &'a str>, point: i32) -> Vec<&'a str> {
+            tree.stab(&point).map(|(_, value)| *value).collect()
+        }
+        assert_eq!(stab(&tree, 12), ["all", "day", "break"]);
+        assert_eq!(stab(&tree, 17), ["all", "evening"]);
+        assert_eq!(stab(&tree, 24), Vec::<&str>::new());
+
+        let overlapping: Vec<_> = tree.overlapping(13..17).map(|(_, value)| *value).collect();
+        assert_eq!(overlapping, ["all", "day"]);
+        assert_eq!(tree.overlapping(5..5).next(), None);
+
+        assert_eq!(tree.remove(&(0..24)), Some("all"));
+        assert_eq!(tree.remove(&(0..24)), None);
+        assert_eq!(tree.get(&(9..17)), Some(&"day"));
+        assert_eq!(stab(&tree, 8), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn overlaps_match_scan() {
+        let mut tree = IntervalTree::new();
+        for i in 0..200 {
+            let start = (i * 37) % 101;
+            let length = 1 + (i * 13) % 17;
+            tree.insert(start..start + length, i);
+        }
+        for i in (0..200).step_by(3) {
+            let start = (i * 37) % 101;
+            let length = 1 + (i * 13) % 17;
+            assert_eq!(tree.remove(&(start..start + length)), Some(i));
+        }
+        let all: Vec<(Range<i32>, i32)> = tree
+            .iter()
+            .map(|(interval, value)| (interval.clone(), *value))
+            .collect();
+        assert_eq!(all.len(), tree.len());
+        for point in -1..120 {
+            let expected: Vec<i32> = all
+                .iter()
+                .filter(|(interval, _)| interval.contains(&point))
+                .map(|(_, value)| *value)
+                .collect();
+            let found: Vec<i32> = tree.stab(&point).map(|(_, value)| *value).collect();
+            assert_eq!(found, expected);
+
+            let range = point..point + 5;
+            let expected: Vec<i32> = all
+                .iter()
+                .filter(|(interval, _)| interval.start < range.end && range.start < interval.end)
+                .map(|(_, value)| *value)
+                .collect();
+            let found: Vec<i32> = tree.overlapping(range).map(|(_, value)| *value).collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn equality_ignores_shape() {
+        let mut ascending = IntervalTree::new();
+        let mut descending = IntervalTree::new();
+        for start in 0..5 {
+            ascending.insert(start..start + 2, start);
+            descending.insert(4 - start..6 - start, 4 - start);
+        }
+        assert_ne!(ascending.root, descending.root);
+        assert_eq!(ascending, descending);
+        descending.insert(0..2, -1);
+        assert_ne!(ascending, descending);
+    }
+
+    #[test]
+    #[should_panic(expected = "interval must not be empty")]
+    fn empty_interval() {
+        IntervalTree::new().insert(3..3, ());
+    }
+}
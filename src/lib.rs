@@ -1,49 +1,332 @@
-use std::cmp::max;
+use std::borrow::Borrow;
+use std::cmp::{max, Ordering};
+use std::collections::VecDeque;
+use std::fmt;
+use std::iter::FusedIterator;
 use std::mem;
+use std::ops::{Bound, RangeBounds};
 
-impl<'a, T: PartialOrd + PartialEq> IntoIterator for &'a BST<T> {
+mod algebra;
+pub mod arena;
+mod augment;
+mod compare;
+mod float;
+pub mod interval;
+pub mod map;
+pub mod multiset;
+pub mod persistent;
+
+pub use algebra::{Difference, Intersection, SymmetricDifference, Union};
+pub use arena::ArenaBST;
+pub use augment::Augment;
+pub use compare::{Comparator, OrdComparator};
+pub use float::{TotalF32, TotalF64};
+pub use interval::IntervalTree;
+pub use map::BSTMap;
+pub use multiset::BSTMultiset;
+pub use persistent::PersistentBST;
+
+impl<'a, T, C, A> IntoIterator for &'a BST<T, C, A> {
     type Item = &'a T;
-    type IntoIter = BSTRefIter<'a, T>;
+    type IntoIter = BSTRefIter<'a, T, A>;
 
     fn into_iter(self) -> Self::IntoIter {
         BSTRefIter::new(self)
     }
 }
 
-enum StackRefMember<'a, T: PartialOrd + PartialEq> {
-    Node(&'a Node<T>),
-    Visited(&'a T),
+/// A shared reference to a subtree of any of the trees in this crate, as
+/// walked by `InOrder`.
+trait Subtree: Copy {
+    type Item;
+    /// The type the tree is ordered by.
+    type Key;
+    /// The entry at the root of the subtree.
+    fn item(self) -> Self::Item;
+    /// The key of the entry at the root of the subtree.
+    fn key(&self) -> &Self::Key;
+    fn left(self) -> Option<Self>;
+    fn right(self) -> Option<Self>;
+    /// Number of entries in the subtree.
+    fn size(self) -> usize;
+    /// Returns the subtree whose root has the smallest key not below `range`.
+    fn first_in<Q: ?Sized, R: RangeBounds<Q>, C: Comparator<Q>>(
+        self,
+        range: &R,
+        cmp: &C,
+    ) -> Option<Self>
+    where
+        Self::Key: Borrow<Q>,
+    {
+        let mut found = None;
+        let mut node = Some(self);
+        while let Some(current) = node {
+            if below_range(current.key().borrow(), range, cmp) {
+                node = current.right();
+            } else {
+                found = Some(current);
+                node = current.left();
+            }
+        }
+        found
+    }
+    /// Returns the subtree whose root has the greatest key not above `range`.
+    fn last_in<Q: ?Sized, R: RangeBounds<Q>, C: Comparator<Q>>(
+        self,
+        range: &R,
+        cmp: &C,
+    ) -> Option<Self>
+    where
+        Self::Key: Borrow<Q>,
+    {
+        let mut found = None;
+        let mut node = Some(self);
+        while let Some(current) = node {
+            if above_range(current.key().borrow(), range, cmp) {
+                node = current.left();
+            } else {
+                found = Some(current);
+                node = current.right();
+            }
+        }
+        found
+    }
+    /// Counts the keys below `range`.
+    fn count_below<Q: ?Sized, R: RangeBounds<Q>, C: Comparator<Q>>(
+        self,
+        range: &R,
+        cmp: &C,
+    ) -> usize
+    where
+        Self::Key: Borrow<Q>,
+    {
+        let mut count = 0;
+        let mut node = Some(self);
+        while let Some(current) = node {
+            if below_range(current.key().borrow(), range, cmp) {
+                count += current.left().map_or(0, Self::size) + 1;
+                node = current.right();
+            } else {
+                node = current.left();
+            }
+        }
+        count
+    }
+    /// Counts the keys above `range`.
+    fn count_above<Q: ?Sized, R: RangeBounds<Q>, C: Comparator<Q>>(
+        self,
+        range: &R,
+        cmp: &C,
+    ) -> usize
+    where
+        Self::Key: Borrow<Q>,
+    {
+        let mut count = 0;
+        let mut node = Some(self);
+        while let Some(current) = node {
+            if above_range(current.key().borrow(), range, cmp) {
+                count += current.right().map_or(0, Self::size) + 1;
+                node = current.left();
+            } else {
+                node = current.right();
+            }
+        }
+        count
+    }
+}
+
+impl<'a, K, V, A> Subtree for &'a Node<K, V, A> {
+    type Item = (&'a K, &'a V);
+    type Key = K;
+    fn item(self) -> (&'a K, &'a V) {
+        (&self.key, &self.value)
+    }
+    fn key(&self) -> &K {
+        &self.key
+    }
+    fn left(self) -> Option<&'a Node<K, V, A>> {
+        self.left.as_deref()
+    }
+    fn right(self) -> Option<&'a Node<K, V, A>> {
+        self.right.as_deref()
+    }
+    fn size(self) -> usize {
+        self.shape.size()
+    }
+}
+
+enum StackRefMember<S> {
+    Node(S),
+    Visited(S),
 }
 
-pub struct BSTRefIter<'a, T: PartialOrd + PartialEq> {
-    stack: Vec<StackRefMember<'a, T>>,
+/// In-order iterator over the entries of a tree.
+///
+/// The deque holds the not yet visited part of the tree in order: subtrees
+/// are expanded at whichever end needs them next, so the front and the back
+/// each take entries from their own side and can never yield one twice.
+struct InOrder<S> {
+    stack: VecDeque<StackRefMember<S>>,
+    /// Number of entries not yet yielded from either end.
+    remaining: usize,
 }
 
-impl<'a, T: PartialOrd + PartialEq> BSTRefIter<'a, T> {
-    fn new(bst: &BST<T>) -> BSTRefIter<T> {
+impl<S: Subtree> InOrder<S> {
+    fn new(root: Option<S>) -> InOrder<S> {
+        InOrder {
+            stack: root.into_iter().map(StackRefMember::Node).collect(),
+            remaining: root.map_or(0, S::size),
+        }
+    }
+    /// Creates an iterator over the entries of the tree rooted at `root` whose
+    /// keys fall into `range`.
+    ///
+    /// The deque is seeded by descending toward both ends of the range, so it
+    /// holds exactly the entries in range and iterating needs no comparisons.
+    fn range<Q: ?Sized, R: RangeBounds<Q>, C: Comparator<Q>>(
+        root: Option<S>,
+        range: &R,
+        cmp: &C,
+    ) -> InOrder<S>
+    where
+        S::Key: Borrow<Q>,
+    {
+        check_range(range, cmp);
         let mut stack = Vec::new();
-        if let Some(root) = &bst.root {
-            stack.push(StackRefMember::Node(root));
+        if let Some(root) = root {
+            InOrder::seed(root, range, cmp, &mut stack);
+        }
+        let remaining = stack
+            .iter()
+            .map(|member| match member {
+                StackRefMember::Node(node) => node.size(),
+                StackRefMember::Visited(_) => 1,
+            })
+            .sum();
+        InOrder {
+            stack: VecDeque::from(stack),
+            remaining,
+        }
+    }
+    fn seed<Q: ?Sized, R: RangeBounds<Q>, C: Comparator<Q>>(
+        node: S,
+        range: &R,
+        cmp: &C,
+        members: &mut Vec<StackRefMember<S>>,
+    ) where
+        S::Key: Borrow<Q>,
+    {
+        let key = node.key().borrow();
+        if below_range(key, range, cmp) {
+            if let Some(right) = node.right() {
+                InOrder::seed(right, range, cmp, members);
+            }
+        } else if above_range(key, range, cmp) {
+            if let Some(left) = node.left() {
+                InOrder::seed(left, range, cmp, members);
+            }
+        } else {
+            if let Some(left) = node.left() {
+                InOrder::seed_from(left, range, cmp, members);
+            }
+            members.push(StackRefMember::Visited(node));
+            if let Some(right) = node.right() {
+                InOrder::seed_to(right, range, cmp, members);
+            }
+        }
+    }
+    /// Seeds the part of a subtree that is not below `range`, knowing that no
+    /// key in it is above `range`.
+    fn seed_from<Q: ?Sized, R: RangeBounds<Q>, C: Comparator<Q>>(
+        node: S,
+        range: &R,
+        cmp: &C,
+        members: &mut Vec<StackRefMember<S>>,
+    ) where
+        S::Key: Borrow<Q>,
+    {
+        if below_range(node.key().borrow(), range, cmp) {
+            if let Some(right) = node.right() {
+                InOrder::seed_from(right, range, cmp, members);
+            }
+        } else {
+            if let Some(left) = node.left() {
+                InOrder::seed_from(left, range, cmp, members);
+            }
+            members.push(StackRefMember::Visited(node));
+            if let Some(right) = node.right() {
+                members.push(StackRefMember::Node(right));
+            }
+        }
+    }
+    /// Seeds the part of a subtree that is not above `range`, knowing that no
+    /// key in it is below `range`.
+    fn seed_to<Q: ?Sized, R: RangeBounds<Q>, C: Comparator<Q>>(
+        node: S,
+        range: &R,
+        cmp: &C,
+        members: &mut Vec<StackRefMember<S>>,
+    ) where
+        S::Key: Borrow<Q>,
+    {
+        if above_range(node.key().borrow(), range, cmp) {
+            if let Some(left) = node.left() {
+                InOrder::seed_to(left, range, cmp, members);
+            }
+        } else {
+            if let Some(left) = node.left() {
+                members.push(StackRefMember::Node(left));
+            }
+            members.push(StackRefMember::Visited(node));
+            if let Some(right) = node.right() {
+                InOrder::seed_to(right, range, cmp, members);
+            }
         }
-        BSTRefIter { stack }
     }
 }
 
-impl<'a, T: PartialOrd + PartialEq> Iterator for BSTRefIter<'a, T> {
-    type Item = &'a T;
-    fn next(&mut self) -> Option<&'a T> {
-        while let Some(stack_member) = self.stack.pop() {
+impl<S: Subtree> Iterator for InOrder<S> {
+    type Item = S::Item;
+    fn next(&mut self) -> Option<S::Item> {
+        while let Some(stack_member) = self.stack.pop_front() {
+            match stack_member {
+                StackRefMember::Visited(node) => {
+                    self.remaining -= 1;
+                    return Some(node.item());
+                }
+                StackRefMember::Node(node) => {
+                    if let Some(right) = node.right() {
+                        self.stack.push_front(StackRefMember::Node(right));
+                    }
+                    self.stack.push_front(StackRefMember::Visited(node));
+                    if let Some(left) = node.left() {
+                        self.stack.push_front(StackRefMember::Node(left));
+                    }
+                }
+            }
+        }
+        None
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<S: Subtree> DoubleEndedIterator for InOrder<S> {
+    fn next_back(&mut self) -> Option<S::Item> {
+        while let Some(stack_member) = self.stack.pop_back() {
             match stack_member {
-                StackRefMember::Visited(value) => {
-                    return Some(value);
+                StackRefMember::Visited(node) => {
+                    self.remaining -= 1;
+                    return Some(node.item());
                 }
                 StackRefMember::Node(node) => {
-                    if let Some(right) = &node.right {
-                        self.stack.push(StackRefMember::Node(right));
+                    if let Some(left) = node.left() {
+                        self.stack.push_back(StackRefMember::Node(left));
                     }
-                    self.stack.push(StackRefMember::Visited(&node.value));
-                    if let Some(left) = &node.left {
-                        self.stack.push(StackRefMember::Node(left));
+                    self.stack.push_back(StackRefMember::Visited(node));
+                    if let Some(right) = node.right() {
+                        self.stack.push_back(StackRefMember::Node(right));
                     }
                 }
             }
@@ -52,369 +335,1897 @@ impl<'a, T: PartialOrd + PartialEq> Iterator for BSTRefIter<'a, T> {
     }
 }
 
-impl<T: PartialOrd + PartialEq> IntoIterator for BST<T> {
+/// In-order iterator over the entries of a `Node` tree.
+type NodeRefIter<'a, K, V, A> = InOrder<&'a Node<K, V, A>>;
+
+/// Panics on ranges `BTreeMap::range` rejects as well.
+fn check_range<Q: ?Sized, R: RangeBounds<Q>, C: Comparator<Q>>(range: &R, cmp: &C) {
+    match (range.start_bound(), range.end_bound()) {
+        (Bound::Excluded(start), Bound::Excluded(end))
+            if cmp.compare(start, end) == Ordering::Equal =>
+        {
+            panic!("range start and end are equal and excluded")
+        }
+        (
+            Bound::Included(start) | Bound::Excluded(start),
+            Bound::Included(end) | Bound::Excluded(end),
+        ) if cmp.compare(start, end) == Ordering::Greater => {
+            panic!("range start is greater than range end")
+        }
+        _ => {}
+    }
+}
+
+fn below_range<Q: ?Sized, R: RangeBounds<Q>, C: Comparator<Q>>(
+    key: &Q,
+    range: &R,
+    cmp: &C,
+) -> bool {
+    match range.start_bound() {
+        Bound::Included(start) => cmp.compare(key, start) == Ordering::Less,
+        Bound::Excluded(start) => cmp.compare(key, start) != Ordering::Greater,
+        Bound::Unbounded => false,
+    }
+}
+
+fn above_range<Q: ?Sized, R: RangeBounds<Q>, C: Comparator<Q>>(
+    key: &Q,
+    range: &R,
+    cmp: &C,
+) -> bool {
+    match range.end_bound() {
+        Bound::Included(end) => cmp.compare(key, end) == Ordering::Greater,
+        Bound::Excluded(end) => cmp.compare(key, end) != Ordering::Less,
+        Bound::Unbounded => false,
+    }
+}
+
+pub struct BSTRefIter<'a, T, A = ()> {
+    inner: NodeRefIter<'a, T, (), A>,
+}
+
+impl<'a, T, A> BSTRefIter<'a, T, A> {
+    fn new<C>(bst: &'a BST<T, C, A>) -> BSTRefIter<'a, T, A> {
+        BSTRefIter {
+            inner: NodeRefIter::new(bst.root.as_deref()),
+        }
+    }
+}
+
+impl<'a, T, A> Iterator for BSTRefIter<'a, T, A> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next().map(|(value, _)| value)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T, A> DoubleEndedIterator for BSTRefIter<'a, T, A> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.inner.next_back().map(|(value, _)| value)
+    }
+}
+
+impl<'a, T, A> ExactSizeIterator for BSTRefIter<'a, T, A> {}
+
+impl<'a, T, A> FusedIterator for BSTRefIter<'a, T, A> {}
+
+/// In-order iterator over the values of a `BST` within a range.
+pub struct Range<'a, T, A = ()> {
+    inner: NodeRefIter<'a, T, (), A>,
+}
+
+impl<'a, T, A> Iterator for Range<'a, T, A> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next().map(|(value, _)| value)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T, A> DoubleEndedIterator for Range<'a, T, A> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.inner.next_back().map(|(value, _)| value)
+    }
+}
+
+impl<'a, T, A> ExactSizeIterator for Range<'a, T, A> {}
+
+impl<'a, T, A> FusedIterator for Range<'a, T, A> {}
+
+impl<T, C, A> IntoIterator for BST<T, C, A> {
     type Item = T;
-    type IntoIter = BSTConsumingIter<T>;
+    type IntoIter = BSTConsumingIter<T, A>;
 
     fn into_iter(self) -> Self::IntoIter {
         BSTConsumingIter::new(self)
     }
 }
 
-pub struct BSTConsumingIter<T: PartialOrd + PartialEq> {
-    stack: Vec<Box<Node<T>>>,
+enum StackMember<K, V, A> {
+    Node(Box<Node<K, V, A>>),
+    Visited(K, V),
 }
 
-impl<T: PartialOrd + PartialEq> BSTConsumingIter<T> {
-    fn new(bst: BST<T>) -> BSTConsumingIter<T> {
-        let mut stack = Vec::new();
-        if let Some(root) = bst.root {
-            stack.push(root);
+/// Consuming counterpart of `NodeRefIter`.
+struct NodeConsumingIter<K, V, A> {
+    stack: VecDeque<StackMember<K, V, A>>,
+    remaining: usize,
+}
+
+impl<K, V, A> NodeConsumingIter<K, V, A> {
+    fn new(root: Option<Box<Node<K, V, A>>>) -> NodeConsumingIter<K, V, A> {
+        let remaining = Node::size_of(&root);
+        let mut stack = VecDeque::new();
+        if let Some(root) = root {
+            stack.push_back(StackMember::Node(root));
         }
-        BSTConsumingIter { stack }
+        NodeConsumingIter { stack, remaining }
     }
 }
 
-impl<T: PartialOrd + PartialEq> Iterator for BSTConsumingIter<T> {
-    type Item = T;
-    fn next(&mut self) -> Option<T> {
-        while let Some(mut node) = self.stack.pop() {
-            match node.left.take() {
-                Some(left) => {
-                    self.stack.push(node);
-                    self.stack.push(left);
+impl<K, V, A> Iterator for NodeConsumingIter<K, V, A> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<(K, V)> {
+        while let Some(stack_member) = self.stack.pop_front() {
+            match stack_member {
+                StackMember::Visited(key, value) => {
+                    self.remaining -= 1;
+                    return Some((key, value));
                 }
-                None => {
-                    if let Some(right) = node.right.take() {
-                        self.stack.push(right);
-                    };
-                    return Some(node.value);
+                StackMember::Node(node) => {
+                    let Node {
+                        key,
+                        value,
+                        left,
+                        right,
+                        ..
+                    } = *node;
+                    if let Some(right) = right {
+                        self.stack.push_front(StackMember::Node(right));
+                    }
+                    self.stack.push_front(StackMember::Visited(key, value));
+                    if let Some(left) = left {
+                        self.stack.push_front(StackMember::Node(left));
+                    }
                 }
             }
         }
         None
     }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct BST<T: PartialOrd + PartialEq> {
-    root: Option<Box<Node<T>>>,
+impl<K, V, A> DoubleEndedIterator for NodeConsumingIter<K, V, A> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        while let Some(stack_member) = self.stack.pop_back() {
+            match stack_member {
+                StackMember::Visited(key, value) => {
+                    self.remaining -= 1;
+                    return Some((key, value));
+                }
+                StackMember::Node(node) => {
+                    let Node {
+                        key,
+                        value,
+                        left,
+                        right,
+                        ..
+                    } = *node;
+                    if let Some(left) = left {
+                        self.stack.push_back(StackMember::Node(left));
+                    }
+                    self.stack.push_back(StackMember::Visited(key, value));
+                    if let Some(right) = right {
+                        self.stack.push_back(StackMember::Node(right));
+                    }
+                }
+            }
+        }
+        None
+    }
 }
 
-impl<T: PartialOrd + PartialEq> Default for BST<T> {
-    fn default() -> Self {
-        Self::new()
+pub struct BSTConsumingIter<T, A = ()> {
+    inner: NodeConsumingIter<T, (), A>,
+}
+
+impl<T, A> BSTConsumingIter<T, A> {
+    fn new<C>(bst: BST<T, C, A>) -> BSTConsumingIter<T, A> {
+        BSTConsumingIter {
+            inner: NodeConsumingIter::new(bst.root),
+        }
     }
 }
 
-impl<T: PartialOrd + PartialEq> BST<T> {
-    pub fn new() -> BST<T> {
-        BST { root: None }
+impl<T, A> Iterator for BSTConsumingIter<T, A> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.inner.next().map(|(value, _)| value)
     }
-    pub fn insert(&mut self, value: T) {
-        match self.root.take() {
-            None => self.root = Node::new_node(value, None, None),
-            Some(root_node) => self.root = root_node.insert(value),
-        };
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
     }
-    pub fn contains(&self, value: T) -> bool {
-        self.root
-            .as_ref()
-            .map_or(false, |root| root.contains(value))
+}
+
+impl<T, A> DoubleEndedIterator for BSTConsumingIter<T, A> {
+    fn next_back(&mut self) -> Option<T> {
+        self.inner.next_back().map(|(value, _)| value)
+    }
+}
+
+impl<T, A> ExactSizeIterator for BSTConsumingIter<T, A> {}
+
+impl<T, A> FusedIterator for BSTConsumingIter<T, A> {}
+
+/// Removal state shared by the `extract_if` iterators.
+///
+/// The tree stays taken apart down to the next entry to test, so walking on
+/// to the entry after a kept one takes O(1) amortized. A removal rebalances
+/// the tree up to the root, after which the walk finds its place again by
+/// position, as removing an entry leaves the position of the next one
+/// unchanged. Dropping the state refreshes the summaries `pred` may have
+/// invalidated by changing values.
+struct NodeExtractIf<'a, K, V, A: Augment<K, V>> {
+    zipper: Zipper<'a, K, V, A>,
+    /// Position of the next entry to test, which is at the focus.
+    index: usize,
+    len: usize,
+}
+
+impl<'a, K, V, A: Augment<K, V>> NodeExtractIf<'a, K, V, A> {
+    fn new(root: &'a mut Link<K, V, A>) -> NodeExtractIf<'a, K, V, A> {
+        let len = Node::size_of(root);
+        let mut extract = NodeExtractIf {
+            zipper: Zipper::new(root),
+            index: 0,
+            len,
+        };
+        extract.zipper.seek(0);
+        extract
     }
-    pub fn delete(&mut self, value: T) {
-        match self.root.take() {
-            None => {}
-            Some(root) if value == root.value => {
-                self.root = Node::delete_by_node(Some(root));
+    /// Moves the focus from a kept entry to the next one, refreshing the
+    /// nodes it passes on the way up.
+    fn advance(&mut self) {
+        self.index += 1;
+        let node = self.zipper.focus.as_mut().expect("focus is at an entry");
+        // `pred` may have changed the value, and summaries see values.
+        node.update_size();
+        if node.right.is_some() {
+            self.zipper.descend(Ordering::Greater);
+            while self
+                .zipper
+                .focus
+                .as_ref()
+                .is_some_and(|node| node.left.is_some())
+            {
+                self.zipper.descend(Ordering::Less);
             }
-            Some(root) => {
-                self.root = root.delete(value);
+            return;
+        }
+        while let Some(side) = self.zipper.ascend() {
+            self.zipper
+                .focus
+                .as_mut()
+                .expect("ascended to a node")
+                .update_size();
+            if side == Ordering::Less {
+                return;
             }
         }
     }
-    pub fn iter(&self) -> BSTRefIter<T> {
-        self.into_iter()
+    /// Removes and returns the next entry for which `pred` returns `true`.
+    fn next<F: FnMut(&K, &mut V) -> bool>(&mut self, pred: &mut F) -> Option<(K, V)> {
+        while self.index < self.len {
+            let node = self.zipper.focus.as_mut().expect("focus is at an entry");
+            if pred(&node.key, &mut node.value) {
+                let (removed, _) = self.zipper.remove_focus();
+                self.len -= 1;
+                self.zipper.seek(self.index);
+                return Some(removed);
+            }
+            self.advance();
+        }
+        None
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.len - self.index))
     }
 }
 
-#[derive(Debug, PartialEq)]
-struct Node<T: PartialOrd + PartialEq> {
-    value: T,
-    left: Option<Box<Node<T>>>,
-    right: Option<Box<Node<T>>>,
-    height: u32,
-    balance_factor: i32,
+impl<K, V, A: Augment<K, V>> Drop for NodeExtractIf<'_, K, V, A> {
+    fn drop(&mut self) {
+        self.zipper.zip(false);
+    }
+}
+
+/// Lazy iterator removing the values of a `BST` that match a predicate,
+/// obtained from `BST::extract_if`.
+///
+/// Values not yet reached when the iterator is dropped stay in the tree.
+pub struct ExtractIf<'a, T, F, A: Augment<T> = ()> {
+    inner: NodeExtractIf<'a, T, (), A>,
+    pred: F,
 }
 
-impl<T: PartialOrd + PartialEq> Node<T> {
-    fn add_left(&mut self, value: T) {
-        self.left = Node::new_node(value, None, None)
+impl<'a, T, F: FnMut(&T) -> bool, A: Augment<T>> Iterator for ExtractIf<'a, T, F, A> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        let pred = &mut self.pred;
+        self.inner
+            .next(&mut |value: &T, _: &mut ()| pred(value))
+            .map(|(value, _)| value)
     }
-    fn add_right(&mut self, value: T) {
-        self.right = Node::new_node(value, None, None)
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
     }
-    fn new_node(
-        value: T,
-        left: Option<Box<Node<T>>>,
-        right: Option<Box<Node<T>>>,
-    ) -> Option<Box<Node<T>>> {
-        let mut new_node = Box::new(Node {
-            value,
-            left,
-            right,
-            height: 0,
-            balance_factor: 0,
-        });
-        new_node.update_height();
-        Some(new_node)
+}
+
+impl<'a, T, F: FnMut(&T) -> bool, A: Augment<T>> FusedIterator for ExtractIf<'a, T, F, A> {}
+
+#[derive(Debug)]
+pub struct BST<T, C = OrdComparator, A = ()> {
+    root: Option<Box<Node<T, (), A>>>,
+    comparator: C,
+}
+
+/// Trees are equal when they hold equal values, whatever their shape.
+impl<T: PartialEq, C, A> PartialEq for BST<T, C, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
     }
-    fn update_height(&mut self) {
-        self.height = max(
-            self.left.as_ref().map_or(0, |v| v.height + 1),
-            self.right.as_ref().map_or(0, |v| v.height + 1),
-        );
-        self.balance_factor = self.get_balance_factor();
-    }
-    fn get_balance_factor(&self) -> i32 {
-        match (&self.left, &self.right) {
-            (None, None) => 0,
-            (None, Some(right)) => (right.height as i32) + 1,
-            (Some(left), None) => -(left.height as i32) - 1,
-            (Some(left), Some(right)) => (right.height as i32) - (left.height as i32),
-        }
-    }
-    fn insert(mut self, value: T) -> Option<Box<Node<T>>> {
-        if value < self.value {
-            match self.left.take() {
-                None => self.add_left(value),
-                Some(boxed) => {
-                    self.left = boxed.insert(value);
-                }
-            }
-        } else if value > self.value {
-            match self.right.take() {
-                None => self.add_right(value),
-                Some(boxed) => {
-                    self.right = boxed.insert(value);
-                }
-            }
-        };
-        self.update_height();
-        self.rotate()
-    }
-    fn rotate(mut self) -> Option<Box<Node<T>>> {
-        match &self.balance_factor {
-            -2 => match self.left.take() {
-                None => panic!("Left can't be None since balance factor is -2"),
-                Some(left) if left.balance_factor > 0 => {
-                    self.left = left.left_rotation();
-                    self.right_rotation()
-                }
-                Some(left) => {
-                    self.left = Some(left);
-                    self.right_rotation()
-                }
-            },
-            -1 => Some(Box::new(self)),
-            0 => Some(Box::new(self)),
-            1 => Some(Box::new(self)),
-            2 => match self.right.take() {
-                None => panic!("Right can't be None since balance factor is 2"),
-                Some(right) if right.balance_factor < 0 => {
-                    self.right = right.right_rotation();
-                    self.left_rotation()
-                }
-                Some(right) => {
-                    self.right = Some(right);
-                    self.left_rotation()
-                }
-            },
-            invalid_bf => panic!(
-                "Balance factor should be from interval [-2, 2], but is {}",
-                invalid_bf
-            ),
-        }
+}
+
+impl<T: Eq, C, A> Eq for BST<T, C, A> {}
+
+impl<T, C: Comparator<T> + Default, A: Augment<T>> Default for BST<T, C, A> {
+    fn default() -> Self {
+        Self::with_augment(C::default())
     }
-    fn contains(&self, value: T) -> bool {
-        if value == self.value {
-            true
-        } else if value < self.value {
-            self.left
-                .as_ref()
-                .map_or(false, |left| left.contains(value))
-        } else {
-            self.right
-                .as_ref()
-                .map_or(false, |right| right.contains(value))
-        }
+}
+
+impl<T: Ord> BST<T> {
+    pub fn new() -> BST<T> {
+        BST::with_comparator(OrdComparator)
     }
-    fn delete(mut self, value: T) -> Option<Box<Node<T>>> {
-        match (self.left.take(), self.right.take()) {
-            (left, Some(right)) if value == right.value => {
-                self.left = left;
-                self.right = Node::delete_by_node(Some(right));
-            }
-            (Some(left), right) if value == left.value => {
-                self.right = right;
-                self.left = Node::delete_by_node(Some(left));
-            }
-            (Some(left), right) if value < self.value => {
-                self.right = right;
-                self.left = left.delete(value)
-            }
-            (left, Some(right)) if value > self.value => {
-                self.left = left;
-                self.right = right.delete(value)
-            }
-            _ => {}
-        };
-        self.update_height();
-        self.rotate()
-    }
-    fn delete_by_node(mut node: Option<Box<Node<T>>>) -> Option<Box<Node<T>>> {
-        match node.take() {
-            None => None,
-            Some(to_delete) => match (to_delete.left, to_delete.right) {
-                (None, None) => None,
-                (None, right) => right,
-                (left, None) => left,
-                (left, Some(mut right)) => match right.delete_get_leftmost() {
-                    None => {
-                        right.left = left;
-                        right.update_height();
-                        right.rotate()
-                    }
-                    Some(mut new_node) => {
-                        right.update_height();
-                        let right = right.rotate();
-                        new_node.right = right;
-                        new_node.left = left;
-                        new_node.update_height();
-                        new_node.rotate()
-                    }
-                },
-            },
+}
+
+impl<T, C: Comparator<T> + Default, A: Augment<T>> BST<T, C, A> {
+    /// Builds a perfectly balanced tree from strictly increasing values in
+    /// O(n).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the values are not strictly increasing.
+    pub fn from_sorted_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let comparator = C::default();
+        let values: Vec<T> = iter.into_iter().collect();
+        assert!(
+            values
+                .windows(2)
+                .all(|pair| comparator.compare(&pair[0], &pair[1]) == Ordering::Less),
+            "values must be strictly increasing"
+        );
+        let len = values.len();
+        BST {
+            root: Node::from_sorted(&mut values.into_iter().map(|value| (value, ())), len),
+            comparator,
         }
     }
-    fn delete_get_leftmost(&mut self) -> Option<Box<Node<T>>> {
-        match &mut self.left {
-            None => None,
-            Some(left) => {
-                let ret_val = match left.delete_get_leftmost() {
-                    None => match left.right.take() {
-                        None => self.left.take(),
-                        Some(right) => Some(mem::replace(left, right)),
-                    },
-                    otherwise => otherwise,
-                };
-                ret_val.and_then(|mut _n| {
-                    _n.update_height();
-                    _n.rotate()
-                })
-            }
+    /// Like `from_sorted_iter`, but trusts the values to be strictly
+    /// increasing.
+    ///
+    /// Values out of order leave the tree unordered, so lookups may miss
+    /// values that are present, as with a comparator that is not a total
+    /// order.
+    pub fn from_sorted_iter_unchecked<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let values: Vec<T> = iter.into_iter().collect();
+        let len = values.len();
+        BST {
+            root: Node::from_sorted(&mut values.into_iter().map(|value| (value, ())), len),
+            comparator: C::default(),
         }
     }
-    fn left_rotation(mut self) -> Option<Box<Node<T>>> {
-        if let Some(mut right) = self.right.take() {
-            self.right = right.left.take();
-            self.update_height();
-            right.left = Some(Box::new(self));
-            right.update_height();
-            return Some(right);
+}
+
+/// Builds the tree in O(n) if the values arrive sorted, and sorts them first
+/// otherwise. The first of several equal values is kept.
+impl<T, C: Comparator<T> + Default, A: Augment<T>> FromIterator<T> for BST<T, C, A> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let comparator = C::default();
+        let entries = iter.into_iter().map(|value| (value, ())).collect();
+        BST {
+            root: Node::from_unsorted(entries, &comparator, false),
+            comparator,
         }
-        None
     }
-    fn right_rotation(mut self) -> Option<Box<Node<T>>> {
-        if let Some(mut left) = self.left.take() {
-            self.left = left.right.take();
-            self.update_height();
-            left.right = Some(Box::new(self));
-            left.update_height();
-            return Some(left);
-        }
-        None
+}
+
+/// Builds a tree of the new values and merges it in with a join-based union.
+/// Values already in the tree are kept.
+impl<T, C: Comparator<T>, A: Augment<T>> Extend<T> for BST<T, C, A> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let entries = iter.into_iter().map(|value| (value, ())).collect();
+        let other = Node::from_unsorted(entries, &self.comparator, false);
+        self.root = Node::union(self.root.take(), other, &self.comparator);
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl<'a, T: Copy + 'a, C: Comparator<T>, A: Augment<T>> Extend<&'a T> for BST<T, C, A> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
 
-    #[test]
-    fn empty() {
-        let bst = BST::new();
-        assert_eq!(bst, BST { root: None });
-        assert_ne!(
-            bst,
-            BST {
-                root: Node::new_node(11, None, None)
-            }
-        );
+impl<T: Ord, const N: usize> From<[T; N]> for BST<T> {
+    fn from(values: [T; N]) -> BST<T> {
+        BST::from_iter(values)
     }
+}
 
-    #[test]
-    fn root_eq() {
-        let mut bst: BST<i32> = BST::new();
-        bst.insert(10);
-        assert_eq!(
-            bst,
-            BST {
-                root: Node::new_node(10, None, None)
-            }
-        );
-        assert_ne!(
-            bst,
-            BST {
-                root: Node::new_node(11, None, None)
-            }
-        );
-        assert_ne!(
-            bst,
-            BST {
-                root: Node::new_node(11, Node::new_node(10, None, None), None)
-            }
-        );
+impl<T, C: Comparator<T>> BST<T, C> {
+    /// Creates an empty tree ordered by `comparator` instead of `Ord`.
+    pub fn with_comparator(comparator: C) -> BST<T, C> {
+        BST::with_augment(comparator)
     }
+}
 
-    #[test]
-    fn insert_simple() {
-        let mut bst: BST<i32> = BST::new();
-        bst.insert(10);
-        assert_eq!(
-            bst,
-            BST {
-                root: Node::new_node(10, None, None,)
-            }
-        );
-        bst.insert(20);
+impl<T, C: Comparator<T>, A: Augment<T>> BST<T, C, A> {
+    /// Creates an empty tree ordered by `comparator` whose nodes keep an `A`
+    /// summary of their subtree.
+    pub fn with_augment(comparator: C) -> BST<T, C, A> {
+        BST {
+            root: None,
+            comparator,
+        }
+    }
+    pub fn comparator(&self) -> &C {
+        &self.comparator
+    }
+    /// Inserts `value`, returning whether it was not yet present.
+    ///
+    /// An equal value already in the tree is kept and `value` is dropped.
+    pub fn insert(&mut self, value: T) -> bool {
+        Node::insert_into(&mut self.root, value, (), &self.comparator).is_none()
+    }
+    /// Inserts `value`, replacing and returning an equal value already present.
+    pub fn replace(&mut self, value: T) -> Option<T> {
+        let mut zipper = Zipper::new(&mut self.root);
+        while let Some(node) = &mut zipper.focus {
+            let step = self.comparator.compare(&value, &node.key);
+            if step == Ordering::Equal {
+                // Summaries see keys, so the new one is summarized upwards.
+                let old = mem::replace(&mut node.key, value);
+                node.update_size();
+                zipper.zip(false);
+                return Some(old);
+            }
+            zipper.descend(step);
+        }
+        zipper.focus = Node::new_entry(value, (), None, None);
+        zipper.zip(true);
+        None
+    }
+    /// Returns `true` if the tree contains a value equal to `value`.
+    ///
+    /// `value` may be any borrowed form of `T`, e.g. `&str` for `BST<String>`.
+    pub fn contains<Q: ?Sized>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.root
+            .as_ref()
+            .is_some_and(|root| root.contains(value, &self.comparator))
+    }
+    /// Returns the stored value equal to `value`, if any.
+    pub fn get<Q: ?Sized>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.root
+            .as_ref()
+            .and_then(|root| root.find(value, &self.comparator))
+            .map(|node| &node.key)
+    }
+    /// Removes the value equal to `value`, returning whether it was present.
+    pub fn remove<Q: ?Sized>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.take(value).is_some()
+    }
+    /// Removes the value equal to `value` and returns the stored one.
+    pub fn take<Q: ?Sized>(&mut self, value: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        Node::delete_from(&mut self.root, value, &self.comparator).map(|(value, _)| value)
+    }
+    /// Returns the smallest value in the tree.
+    pub fn first(&self) -> Option<&T> {
+        self.root.as_ref().map(|root| &root.leftmost().key)
+    }
+    /// Returns the greatest value in the tree.
+    pub fn last(&self) -> Option<&T> {
+        self.root.as_ref().map(|root| &root.rightmost().key)
+    }
+    /// Removes and returns the smallest value in the tree.
+    pub fn pop_first(&mut self) -> Option<T> {
+        Node::delete_first(&mut self.root).map(|(value, _)| value)
+    }
+    /// Removes and returns the greatest value in the tree.
+    pub fn pop_last(&mut self) -> Option<T> {
+        Node::delete_last(&mut self.root).map(|(value, _)| value)
+    }
+    /// Moves all values `>= value` into a new tree, in O(log n).
+    pub fn split_off<Q: ?Sized>(&mut self, value: &Q) -> Self
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q> + Clone,
+    {
+        let (below, found, above) = Node::split(self.root.take(), value, &self.comparator);
+        self.root = below;
+        let above = match found {
+            Some((found, ())) => Node::join(None, found, (), above),
+            None => above,
+        };
+        BST {
+            root: above,
+            comparator: self.comparator.clone(),
+        }
+    }
+    /// Splits the tree into the values below `value`, the value equal to
+    /// `value` if there is one, and the values above `value`, in O(log n).
+    pub fn split<Q: ?Sized>(self, value: &Q) -> (Self, Option<T>, Self)
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q> + Clone,
+    {
+        let (below, found, above) = Node::split(self.root, value, &self.comparator);
+        (
+            BST {
+                root: below,
+                comparator: self.comparator.clone(),
+            },
+            found.map(|(found, ())| found),
+            BST {
+                root: above,
+                comparator: self.comparator,
+            },
+        )
+    }
+    /// Joins `left`, `pivot` and `right` into one tree, in O(log n).
+    ///
+    /// The tree is ordered by the comparator of `left`.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless all values of `left` are smaller than `pivot` and all
+    /// values of `right` are greater.
+    pub fn join(left: Self, pivot: T, right: Self) -> Self {
+        let cmp = &left.comparator;
+        assert!(
+            left.last()
+                .is_none_or(|last| cmp.compare(last, &pivot) == Ordering::Less)
+                && right
+                    .first()
+                    .is_none_or(|first| cmp.compare(&pivot, first) == Ordering::Less),
+            "joined trees must be ordered around the pivot"
+        );
+        BST {
+            root: Node::join(left.root, pivot, (), right.root),
+            comparator: left.comparator,
+        }
+    }
+    /// Moves all values of `other` into `self`, leaving `other` empty.
+    ///
+    /// Values of `other` replace equal ones of `self`. Takes O(log n) when all
+    /// values of one tree are smaller than all values of the other, and
+    /// O(m log(n / m + 1)) otherwise, for trees of sizes m <= n.
+    pub fn append(&mut self, other: &mut Self) {
+        self.root = Node::append(self.root.take(), other.root.take(), &self.comparator);
+    }
+    /// Keeps only the values for which `f` returns `true`, visiting them in
+    /// order.
+    ///
+    /// The tree is rebuilt by joining the kept values in a single O(n) pass.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.root = Node::retain(self.root.take(), &mut |value: &T, _: &mut ()| f(value));
+    }
+    /// Lazily removes and yields, in order, the values for which `pred`
+    /// returns `true`.
+    ///
+    /// Each removal rebalances the tree in O(log n), while kept values take
+    /// O(1) amortized.
+    pub fn extract_if<F: FnMut(&T) -> bool>(&mut self, pred: F) -> ExtractIf<'_, T, F, A> {
+        ExtractIf {
+            inner: NodeExtractIf::new(&mut self.root),
+            pred,
+        }
+    }
+    /// Returns the smallest value above `bound`.
+    ///
+    /// `Included(x)` finds the first value `>= x`, `Excluded(x)` the first
+    /// value `> x` and `Unbounded` the first value overall.
+    pub fn lower_bound<Q: ?Sized>(&self, bound: Bound<&Q>) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.root
+            .as_ref()
+            .and_then(|root| root.first_in(&(bound, Bound::Unbounded), &self.comparator))
+            .map(|node| &node.key)
+    }
+    /// Returns the greatest value below `bound`.
+    ///
+    /// `Included(x)` finds the last value `<= x`, `Excluded(x)` the last
+    /// value `< x` and `Unbounded` the last value overall.
+    pub fn upper_bound<Q: ?Sized>(&self, bound: Bound<&Q>) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.root
+            .as_ref()
+            .and_then(|root| root.last_in(&(Bound::Unbounded, bound), &self.comparator))
+            .map(|node| &node.key)
+    }
+    /// Returns a cursor at the value `lower_bound` would return, or at the
+    /// ghost if there is none.
+    pub fn lower_bound_cursor<Q: ?Sized>(&self, bound: Bound<&Q>) -> Cursor<'_, T, A>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        Cursor {
+            inner: map::Cursor::new(&self.root, self.lower_bound_index(bound)),
+        }
+    }
+    /// Returns a cursor at the value `upper_bound` would return, or at the
+    /// ghost if there is none.
+    pub fn upper_bound_cursor<Q: ?Sized>(&self, bound: Bound<&Q>) -> Cursor<'_, T, A>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        Cursor {
+            inner: map::Cursor::new(&self.root, self.upper_bound_index(bound)),
+        }
+    }
+    /// Like `lower_bound_cursor`, but the cursor can edit the tree.
+    pub fn lower_bound_cursor_mut<Q: ?Sized>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, T, C, A>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        let index = self.lower_bound_index(bound);
+        CursorMut {
+            inner: map::CursorMut::new(&mut self.root, &self.comparator, index),
+        }
+    }
+    /// Like `upper_bound_cursor`, but the cursor can edit the tree.
+    pub fn upper_bound_cursor_mut<Q: ?Sized>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, T, C, A>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        let index = self.upper_bound_index(bound);
+        CursorMut {
+            inner: map::CursorMut::new(&mut self.root, &self.comparator, index),
+        }
+    }
+    fn lower_bound_index<Q: ?Sized>(&self, bound: Bound<&Q>) -> usize
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.root.as_ref().map_or(0, |root| {
+            root.count_below(&(bound, Bound::Unbounded), &self.comparator)
+        })
+    }
+    fn upper_bound_index<Q: ?Sized>(&self, bound: Bound<&Q>) -> usize
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        let len = self.len();
+        let not_above = self.root.as_ref().map_or(0, |root| {
+            len - root.count_above(&(Bound::Unbounded, bound), &self.comparator)
+        });
+        not_above.checked_sub(1).unwrap_or(len)
+    }
+    /// Returns the greatest value `<= value`.
+    pub fn floor<Q: ?Sized>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.upper_bound(Bound::Included(value))
+    }
+    /// Returns the smallest value `>= value`.
+    pub fn ceiling<Q: ?Sized>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.lower_bound(Bound::Included(value))
+    }
+    /// Returns the greatest value `< value`.
+    pub fn predecessor<Q: ?Sized>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.upper_bound(Bound::Excluded(value))
+    }
+    /// Returns the smallest value `> value`.
+    pub fn successor<Q: ?Sized>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.lower_bound(Bound::Excluded(value))
+    }
+    /// Returns the number of values smaller than `value`, which is also the
+    /// position `value` has, or would have, in iteration order.
+    pub fn rank<Q: ?Sized>(&self, value: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.root.as_ref().map_or(0, |root| {
+            root.count_below(
+                &(Bound::Included(value), Bound::Unbounded),
+                &self.comparator,
+            )
+        })
+    }
+    /// Returns the number of values within `range` in O(log n).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end, or if both
+    /// are equal and excluded.
+    pub fn count_in_range<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> usize
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        check_range(&range, &self.comparator);
+        self.root.as_ref().map_or(0, |root| {
+            root.size()
+                - root.count_below(&range, &self.comparator)
+                - root.count_above(&range, &self.comparator)
+        })
+    }
+    /// Combines the summaries of the values within `range` in O(log n).
+    ///
+    /// Returns `None` if the range is empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end, or if both
+    /// are equal and excluded.
+    pub fn fold_range<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> Option<A>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        check_range(&range, &self.comparator);
+        self.root
+            .as_ref()
+            .and_then(|root| root.fold_range(&range, &self.comparator))
+    }
+    /// Iterates in order over the values within `range`.
+    ///
+    /// Takes O(log n) to find both ends of the range, plus O(1) amortized per
+    /// value returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end, or if both
+    /// are equal and excluded.
+    pub fn range<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> Range<'_, T, A>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        Range {
+            inner: NodeRefIter::range(self.root.as_deref(), &range, &self.comparator),
+        }
+    }
+    /// Gets the entry for `value` for in-place manipulation.
+    pub fn entry(&mut self, value: T) -> Entry<'_, T, A> {
+        match map::Entry::search(&mut self.root, value, &self.comparator) {
+            map::Entry::Occupied(inner) => Entry::Occupied(OccupiedEntry { inner }),
+            map::Entry::Vacant(inner) => Entry::Vacant(VacantEntry { inner }),
+        }
+    }
+}
+
+impl<T, C, A> BST<T, C, A> {
+    pub fn iter(&self) -> BSTRefIter<'_, T, A> {
+        self.into_iter()
+    }
+    /// Returns the number of values in the tree.
+    pub fn len(&self) -> usize {
+        Node::size_of(&self.root)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+    /// Returns the summary of all values in the tree.
+    pub fn summary(&self) -> Option<&A> {
+        self.root.as_ref().map(|root| &root.summary)
+    }
+    /// Returns the value at position `index` in iteration order, in O(log n).
+    pub fn nth(&self, index: usize) -> Option<&T> {
+        self.root
+            .as_ref()
+            .and_then(|root| root.select(index))
+            .map(|node| &node.key)
+    }
+}
+
+/// A view into a single element of a `BST`, obtained from `BST::entry`.
+pub enum Entry<'a, T, A: Augment<T> = ()> {
+    Occupied(OccupiedEntry<'a, T, A>),
+    Vacant(VacantEntry<'a, T, A>),
+}
+
+/// An entry whose value is present in the tree.
+pub struct OccupiedEntry<'a, T, A: Augment<T> = ()> {
+    inner: map::OccupiedEntry<'a, T, (), A>,
+}
+
+/// An entry whose value is missing from the tree.
+pub struct VacantEntry<'a, T, A: Augment<T> = ()> {
+    inner: map::VacantEntry<'a, T, (), A>,
+}
+
+impl<'a, T, A: Augment<T>> Entry<'a, T, A> {
+    pub fn get(&self) -> &T {
+        match self {
+            Entry::Occupied(entry) => entry.get(),
+            Entry::Vacant(entry) => entry.get(),
+        }
+    }
+    /// Inserts the value if the entry is vacant, and returns a reference to
+    /// the value stored in the tree.
+    pub fn or_insert(self) -> &'a T {
+        match self {
+            Entry::Occupied(entry) => entry.into_ref(),
+            Entry::Vacant(entry) => entry.insert(),
+        }
+    }
+}
+
+impl<'a, T, A: Augment<T>> OccupiedEntry<'a, T, A> {
+    pub fn get(&self) -> &T {
+        self.inner.key()
+    }
+    /// Converts the entry into a reference to the stored value.
+    pub fn into_ref(self) -> &'a T {
+        self.inner.into_key_ref()
+    }
+    /// Removes the value from the tree and returns the stored one.
+    pub fn remove(self) -> T {
+        self.inner.remove_entry().0
+    }
+}
+
+impl<'a, T, A: Augment<T>> VacantEntry<'a, T, A> {
+    pub fn get(&self) -> &T {
+        self.inner.key()
+    }
+    pub fn into_value(self) -> T {
+        self.inner.into_key()
+    }
+    /// Inserts the value and returns a reference to it.
+    pub fn insert(self) -> &'a T {
+        self.inner.insert_key_ref(())
+    }
+    /// Inserts the value and returns an entry for it.
+    pub fn insert_entry(self) -> OccupiedEntry<'a, T, A> {
+        OccupiedEntry {
+            inner: self.inner.insert_entry(()),
+        }
+    }
+}
+
+/// A cursor over the values of a `BST`, obtained from
+/// `BST::lower_bound_cursor` or `BST::upper_bound_cursor`.
+///
+/// It moves like `map::Cursor`, through a ghost position between the last
+/// and the first value.
+pub struct Cursor<'a, T, A = ()> {
+    inner: map::Cursor<'a, T, (), A>,
+}
+
+impl<'a, T, A> Clone for Cursor<'a, T, A> {
+    fn clone(&self) -> Self {
+        Cursor {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, T, A> Cursor<'a, T, A> {
+    /// Returns the position of the current value in iteration order, or
+    /// `None` at the ghost.
+    pub fn index(&self) -> Option<usize> {
+        self.inner.index()
+    }
+    pub fn move_next(&mut self) {
+        self.inner.move_next();
+    }
+    pub fn move_prev(&mut self) {
+        self.inner.move_prev();
+    }
+    /// Returns the current value, or `None` at the ghost.
+    pub fn peek(&self) -> Option<&'a T> {
+        self.inner.peek().map(|(value, _)| value)
+    }
+    pub fn peek_next(&self) -> Option<&'a T> {
+        self.inner.peek_next().map(|(value, _)| value)
+    }
+    pub fn peek_prev(&self) -> Option<&'a T> {
+        self.inner.peek_prev().map(|(value, _)| value)
+    }
+}
+
+/// A cursor that can also edit a `BST`, obtained from
+/// `BST::lower_bound_cursor_mut` or `BST::upper_bound_cursor_mut`.
+pub struct CursorMut<'a, T, C, A = ()> {
+    inner: map::CursorMut<'a, T, (), C, A>,
+}
+
+impl<'a, T, C: Comparator<T>, A: Augment<T>> CursorMut<'a, T, C, A> {
+    /// Returns a read-only cursor at the same position.
+    pub fn as_cursor(&self) -> Cursor<'_, T, A> {
+        Cursor {
+            inner: self.inner.as_cursor(),
+        }
+    }
+    /// Returns the position of the current value in iteration order, or
+    /// `None` at the ghost.
+    pub fn index(&self) -> Option<usize> {
+        self.inner.index()
+    }
+    pub fn move_next(&mut self) {
+        self.inner.move_next();
+    }
+    pub fn move_prev(&mut self) {
+        self.inner.move_prev();
+    }
+    /// Returns the current value, or `None` at the ghost.
+    pub fn peek(&self) -> Option<&T> {
+        self.as_cursor().peek()
+    }
+    pub fn peek_next(&self) -> Option<&T> {
+        self.as_cursor().peek_next()
+    }
+    pub fn peek_prev(&self) -> Option<&T> {
+        self.as_cursor().peek_prev()
+    }
+    /// Removes and returns the current value, moving the cursor to the next
+    /// one. Does nothing at the ghost.
+    pub fn remove_current(&mut self) -> Option<T> {
+        self.inner.remove_current().map(|(value, _)| value)
+    }
+    /// Inserts `value` between the previous value and the current one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is not greater than the previous value and smaller
+    /// than the current one.
+    pub fn insert_before(&mut self, value: T) {
+        self.inner.insert_before(value, ());
+    }
+    /// Inserts `value` between the current value and the next one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is not greater than the current value and smaller
+    /// than the next one.
+    pub fn insert_after(&mut self, value: T) {
+        self.inner.insert_after(value, ());
+    }
+}
+
+type Link<K, V, A = ()> = Option<Box<Node<K, V, A>>>;
+
+/// Most links on a path from the root of an AVL tree to an empty link. A
+/// tree that deep would hold more than `usize::MAX` nodes.
+const MAX_DEPTH: usize = 96;
+
+/// A tree taken apart along the path from its root down to one link, so
+/// that the nodes on the path can be updated bottom-up.
+///
+/// Each node on the path is detached from its parent. The child link the
+/// path continues through holds the node's parent instead, so `path` is the
+/// deepest of them and the focused subtree hangs below none. Taking a tree
+/// apart thus needs no memory besides the tree. Dropping the zipper links
+/// everything back under the root it was made from, so the tree stays whole
+/// even if a comparator panics on the way down. Forgetting it leaks the
+/// tree and leaves the root empty.
+struct Zipper<'a, K, V, A: Augment<K, V>> {
+    /// The link the tree was taken from, until it is put back.
+    root: Option<&'a mut Link<K, V, A>>,
+    path: Link<K, V, A>,
+    /// Bit `i` is set if the path turns right below the node at depth `i`,
+    /// which covers paths up to `MAX_DEPTH` long.
+    right_turns: u128,
+    depth: usize,
+    focus: Link<K, V, A>,
+    /// Whether the focused entry changed in place, so that the sizes and
+    /// summaries above it need refreshing when the tree is put back.
+    dirty: bool,
+}
+
+const _: () = assert!(MAX_DEPTH <= u128::BITS as usize);
+
+impl<'a, K, V, A: Augment<K, V>> Zipper<'a, K, V, A> {
+    fn new(root: &'a mut Link<K, V, A>) -> Zipper<'a, K, V, A> {
+        let focus = root.take();
+        Zipper {
+            root: Some(root),
+            path: None,
+            right_turns: 0,
+            depth: 0,
+            focus,
+            dirty: false,
+        }
+    }
+    /// The focused node, which must exist.
+    fn node(&self) -> &Node<K, V, A> {
+        self.focus.as_deref().expect("focus should hold a node")
+    }
+    /// The focused node, which must exist. Sizes and summaries above it are
+    /// refreshed when the tree is put back.
+    fn node_mut(&mut self) -> &mut Node<K, V, A> {
+        self.dirty = true;
+        self.focus.as_deref_mut().expect("focus should hold a node")
+    }
+    /// The side the path turns to below the node at depth `level`.
+    fn turn(right_turns: u128, level: usize) -> Ordering {
+        if right_turns >> level & 1 == 1 {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        }
+    }
+    /// Moves the focus to the `step` child of the focused node.
+    fn descend(&mut self, step: Ordering) {
+        let mut node = self
+            .focus
+            .take()
+            .expect("descended link should hold a node");
+        let child = node.child_link(step);
+        self.focus = mem::replace(child, self.path.take());
+        self.path = Some(node);
+        if step == Ordering::Greater {
+            self.right_turns |= 1 << self.depth;
+        } else {
+            self.right_turns &= !(1 << self.depth);
+        }
+        self.depth += 1;
+    }
+    /// Moves the focus to the parent of the focused link, linking the focus
+    /// back under it. Returns the side the focus was on, or `None` at the
+    /// root.
+    fn ascend(&mut self) -> Option<Ordering> {
+        let mut parent = self.path.take()?;
+        self.depth -= 1;
+        let side = Zipper::<K, V, A>::turn(self.right_turns, self.depth);
+        self.path = mem::replace(parent.child_link(side), self.focus.take());
+        self.focus = Some(parent);
+        Some(side)
+    }
+    /// Moves the focus from the focused node down to the entry at position
+    /// `index` within its subtree, or to the empty link after the last one.
+    fn seek(&mut self, mut index: usize) {
+        while let Some(node) = &self.focus {
+            let left = Node::size_of(&node.left);
+            match index.cmp(&left) {
+                Ordering::Less => self.descend(Ordering::Less),
+                Ordering::Equal => return,
+                Ordering::Greater => {
+                    index -= left + 1;
+                    self.descend(Ordering::Greater);
+                }
+            }
+        }
+    }
+    /// Puts the tree back together after the subtree at the focus changed,
+    /// rebalancing on the way up. Returns whether the height of the whole
+    /// tree changed.
+    ///
+    /// Once a subtree's height is unchanged nothing above it can be out of
+    /// balance, so rotations and balance checks stop there. Sizes and
+    /// summaries still change on every level, so each node up to the root
+    /// is updated regardless.
+    fn zip(&mut self, mut height_changed: bool) -> bool {
+        while self.ascend().is_some() {
+            height_changed = Node::retrace(&mut self.focus, height_changed);
+        }
+        self.dirty = false;
+        height_changed
+    }
+    /// Links a new entry at the empty focused link and puts the tree back
+    /// together. Returns the position of the new entry in key order.
+    fn insert_focus(&mut self, key: K, value: V) -> usize {
+        self.focus = Node::new_entry(key, value, None, None);
+        let mut index = 0;
+        let mut height_changed = true;
+        while let Some(side) = self.ascend() {
+            // Rotating a subtree keeps the position of the entry within it.
+            if side == Ordering::Greater {
+                index += Node::size_of(&self.node().left) + 1;
+            }
+            height_changed = Node::retrace(&mut self.focus, height_changed);
+        }
+        index
+    }
+    /// Removes the focused node and puts the tree back together. Returns the
+    /// removed entry and whether the height of the whole tree changed.
+    fn remove_focus(&mut self) -> ((K, V), bool) {
+        let target = self.depth;
+        let node = self.node();
+        if node.left.is_some() && node.right.is_some() {
+            // The node trades its entry with its in-order successor, which
+            // is unlinked in its place.
+            self.descend(Ordering::Greater);
+            while self.focus.as_ref().is_some_and(|node| node.left.is_some()) {
+                self.descend(Ordering::Less);
+            }
+        }
+        let mut unlinked = self.focus.take().expect("removed link should hold a node");
+        // An AVL node with one child is the parent of a leaf, so unlinking
+        // it always shrinks the subtree.
+        self.focus = unlinked.left.take().or_else(|| unlinked.right.take());
+        let mut height_changed = true;
+        while self.depth > target && self.ascend().is_some() {
+            if self.depth == target {
+                let node = self.focus.as_mut().expect("ascended to a node");
+                mem::swap(&mut node.key, &mut unlinked.key);
+                mem::swap(&mut node.value, &mut unlinked.value);
+            }
+            height_changed = Node::retrace(&mut self.focus, height_changed);
+        }
+        let Node { key, value, .. } = *unlinked;
+        ((key, value), self.zip(height_changed))
+    }
+    /// Puts the tree back together, refreshing the path if the focused entry
+    /// changed, and returns the link it hangs from again.
+    fn close(&mut self) -> Option<&'a mut Link<K, V, A>> {
+        let root = self.root.take()?;
+        if self.dirty {
+            self.zip(false);
+        } else {
+            while self.ascend().is_some() {}
+        }
+        *root = self.focus.take();
+        Some(root)
+    }
+    /// Puts the tree back together and returns the link it hangs from.
+    fn into_root(mut self) -> &'a mut Link<K, V, A> {
+        self.close().expect("zipper holds the tree until closed")
+    }
+    /// Puts the tree back together and returns the node that was focused,
+    /// found again by the turns of the path.
+    fn into_node(self) -> &'a mut Node<K, V, A> {
+        let (depth, right_turns) = (self.depth, self.right_turns);
+        let mut node = self
+            .into_root()
+            .as_deref_mut()
+            .expect("focus should hold a node");
+        for level in 0..depth {
+            node = node
+                .child_link(Zipper::<K, V, A>::turn(right_turns, level))
+                .as_deref_mut()
+                .expect("path should lead to the focus");
+        }
+        node
+    }
+}
+
+impl<K, V, A: Augment<K, V>> Drop for Zipper<'_, K, V, A> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// The entries below a key, the entry equal to it and the entries above it.
+type Split<K, V, A> = (Link<K, V, A>, Option<(K, V)>, Link<K, V, A>);
+
+/// A tree node holding a `key` and the `value` attached to it.
+///
+/// `BST<T>` stores its elements as keys with a `()` value, `BSTMap<K, V>`
+/// uses both, so the AVL balancing below is shared by the two. Keys are
+/// ordered by the comparator the tree passes down to each operation.
+#[derive(Debug, PartialEq)]
+struct Node<K, V, A = ()> {
+    key: K,
+    value: V,
+    left: Option<Box<Node<K, V, A>>>,
+    right: Option<Box<Node<K, V, A>>>,
+    shape: Shape,
+    summary: A,
+}
+
+/// Height and size of the subtree rooted at a node, packed into one word.
+///
+/// The height of a leaf is 0 and never exceeds `MAX_DEPTH`, so it fits the
+/// top 8 bits, leaving 56 for the number of nodes, this one included. The
+/// balance factor is not stored, but derived from the children's heights.
+#[derive(Clone, Copy, PartialEq)]
+struct Shape(u64);
+
+impl Shape {
+    const SIZE_BITS: u32 = 56;
+
+    /// # Panics
+    ///
+    /// Panics if `size` needs more than 56 bits, rather than silently
+    /// corrupting the height. Only a tree of more nodes than fit into memory
+    /// gets that large.
+    fn new(height: u32, size: usize) -> Shape {
+        debug_assert!(height < 1 << (u64::BITS - Shape::SIZE_BITS));
+        assert!(
+            (size as u64) < 1 << Shape::SIZE_BITS,
+            "tree size exceeds the shape's size bits"
+        );
+        Shape(u64::from(height) << Shape::SIZE_BITS | size as u64)
+    }
+    fn height(self) -> u32 {
+        (self.0 >> Shape::SIZE_BITS) as u32
+    }
+    fn size(self) -> usize {
+        (self.0 & ((1 << Shape::SIZE_BITS) - 1)) as usize
+    }
+}
+
+impl fmt::Debug for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shape")
+            .field("height", &self.height())
+            .field("size", &self.size())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+impl<T> Node<T, ()> {
+    fn new_node(
+        value: T,
+        left: Option<Box<Node<T, ()>>>,
+        right: Option<Box<Node<T, ()>>>,
+    ) -> Option<Box<Node<T, ()>>> {
+        Node::new_entry(value, (), left, right)
+    }
+}
+
+impl<K, V, A> Node<K, V, A> {
+    fn size_of(tree: &Link<K, V, A>) -> usize {
+        tree.as_ref().map_or(0, |node| node.size())
+    }
+    /// Height of the tree rooted at `tree`, counting an empty tree as 0 and
+    /// a leaf as 1.
+    fn height_of(tree: &Link<K, V, A>) -> u32 {
+        tree.as_ref().map_or(0, |node| node.height() + 1)
+    }
+    /// Number of nodes in the subtree rooted here, this one included.
+    fn size(&self) -> usize {
+        self.shape.size()
+    }
+    /// Height of the subtree rooted here, 0 for a leaf.
+    fn height(&self) -> u32 {
+        self.shape.height()
+    }
+    /// Height of the right subtree minus that of the left one.
+    fn balance_factor(&self) -> i32 {
+        Node::height_of(&self.right) as i32 - Node::height_of(&self.left) as i32
+    }
+    /// Returns the node at position `index` in key order, counting from 0.
+    fn select(&self, mut index: usize) -> Option<&Node<K, V, A>> {
+        let mut node = Some(self);
+        while let Some(current) = node {
+            let left = Node::size_of(&current.left);
+            match index.cmp(&left) {
+                Ordering::Less => node = current.left.as_deref(),
+                Ordering::Equal => return Some(current),
+                Ordering::Greater => {
+                    index -= left + 1;
+                    node = current.right.as_deref();
+                }
+            }
+        }
+        None
+    }
+    fn select_mut(&mut self, mut index: usize) -> Option<&mut Node<K, V, A>> {
+        let mut node = Some(self);
+        while let Some(current) = node {
+            let left = Node::size_of(&current.left);
+            match index.cmp(&left) {
+                Ordering::Less => node = current.left.as_deref_mut(),
+                Ordering::Equal => return Some(current),
+                Ordering::Greater => {
+                    index -= left + 1;
+                    node = current.right.as_deref_mut();
+                }
+            }
+        }
+        None
+    }
+    fn child(&self, step: Ordering) -> Option<&Node<K, V, A>> {
+        match step {
+            Ordering::Less => self.left.as_deref(),
+            _ => self.right.as_deref(),
+        }
+    }
+    fn child_link(&mut self, step: Ordering) -> &mut Link<K, V, A> {
+        match step {
+            Ordering::Less => &mut self.left,
+            _ => &mut self.right,
+        }
+    }
+}
+
+impl<K, V, A: Augment<K, V>> Node<K, V, A> {
+    fn new_entry(key: K, value: V, left: Link<K, V, A>, right: Link<K, V, A>) -> Link<K, V, A> {
+        let mut new_node = Box::new(Node {
+            summary: A::summarize(&key, &value),
+            key,
+            value,
+            left,
+            right,
+            shape: Shape::new(0, 1),
+        });
+        new_node.update_height();
+        Some(new_node)
+    }
+    /// Recomputes height, size and summary after the children changed.
+    fn update_height(&mut self) {
+        let height = max(Node::height_of(&self.left), Node::height_of(&self.right));
+        self.update_shape(height);
+    }
+    /// Recomputes size and summary, which change with every insertion or
+    /// deletion below this node even when its height does not.
+    fn update_size(&mut self) {
+        self.update_shape(self.height());
+    }
+    /// Stores `height` with the recomputed size and refreshes the summary.
+    fn update_shape(&mut self, height: u32) {
+        let size = 1 + Node::size_of(&self.left) + Node::size_of(&self.right);
+        self.shape = Shape::new(height, size);
+        let own = A::summarize(&self.key, &self.value);
+        let summary = match &self.left {
+            Some(left) => left.summary.combine(&own),
+            None => own,
+        };
+        self.summary = match &self.right {
+            Some(right) => summary.combine(&right.summary),
+            None => summary,
+        };
+    }
+    /// Inserts `key` into the (possibly empty) tree rooted at `tree`.
+    ///
+    /// Returns the value previously stored under `key`.
+    fn insert_into<C: Comparator<K>>(
+        tree: &mut Link<K, V, A>,
+        key: K,
+        value: V,
+        cmp: &C,
+    ) -> Option<V> {
+        let mut zipper = Zipper::new(tree);
+        while let Some(node) = &mut zipper.focus {
+            let step = cmp.compare(&key, &node.key);
+            if step == Ordering::Equal {
+                // Summaries see values, so the new one is summarized upwards.
+                let old = mem::replace(&mut node.value, value);
+                node.update_size();
+                zipper.zip(false);
+                return Some(old);
+            }
+            zipper.descend(step);
+        }
+        zipper.focus = Node::new_entry(key, value, None, None);
+        zipper.zip(true);
+        None
+    }
+    /// Updates the node at `link` after an entry was added to or removed from
+    /// one of its subtrees.
+    ///
+    /// Balance can only be lost if the height of that subtree changed, so
+    /// otherwise only the size and summary are refreshed. Returns whether the
+    /// height of the subtree at `link` changed in turn.
+    fn retrace(link: &mut Link<K, V, A>, child_height_changed: bool) -> bool {
+        let node = link.as_mut().expect("retraced link should hold a node");
+        if !child_height_changed {
+            node.update_size();
+            return false;
+        }
+        let height = node.height();
+        Node::rebalance(link);
+        link.as_ref().is_some_and(|node| node.height() != height)
+    }
+    /// Updates the node at `link` and rotates it in place if its subtrees'
+    /// heights differ by two.
+    fn rebalance(link: &mut Link<K, V, A>) {
+        let node = link.as_mut().expect("rebalanced link should hold a node");
+        node.update_height();
+        match node.balance_factor() {
+            -2 => {
+                if node
+                    .left
+                    .as_ref()
+                    .is_some_and(|left| left.balance_factor() > 0)
+                {
+                    Node::rotate_left(&mut node.left);
+                }
+                Node::rotate_right(link);
+            }
+            2 => {
+                if node
+                    .right
+                    .as_ref()
+                    .is_some_and(|right| right.balance_factor() < 0)
+                {
+                    Node::rotate_right(&mut node.right);
+                }
+                Node::rotate_left(link);
+            }
+            -1..=1 => {}
+            invalid_bf => panic!(
+                "Balance factor should be from interval [-2, 2], but is {}",
+                invalid_bf
+            ),
+        }
+    }
+    /// Lifts the right child of the node at `link` into its place.
+    fn rotate_left(link: &mut Link<K, V, A>) {
+        let mut node = link.take().expect("rotated link should hold a node");
+        let mut right = node
+            .right
+            .take()
+            .expect("Right can't be None when rotating left");
+        node.right = right.left.take();
+        node.update_height();
+        right.left = Some(node);
+        right.update_height();
+        *link = Some(right);
+    }
+    /// Lifts the left child of the node at `link` into its place.
+    fn rotate_right(link: &mut Link<K, V, A>) {
+        let mut node = link.take().expect("rotated link should hold a node");
+        let mut left = node
+            .left
+            .take()
+            .expect("Left can't be None when rotating right");
+        node.left = left.right.take();
+        node.update_height();
+        left.right = Some(node);
+        left.update_height();
+        *link = Some(left);
+    }
+    fn contains<Q: ?Sized, C: Comparator<Q>>(&self, key: &Q, cmp: &C) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.find(key, cmp).is_some()
+    }
+    fn find<Q: ?Sized, C: Comparator<Q>>(&self, key: &Q, cmp: &C) -> Option<&Node<K, V, A>>
+    where
+        K: Borrow<Q>,
+    {
+        match cmp.compare(key, self.key.borrow()) {
+            Ordering::Equal => Some(self),
+            Ordering::Less => self.left.as_ref().and_then(|left| left.find(key, cmp)),
+            Ordering::Greater => self.right.as_ref().and_then(|right| right.find(key, cmp)),
+        }
+    }
+    fn find_mut<Q: ?Sized, C: Comparator<Q>>(
+        &mut self,
+        key: &Q,
+        cmp: &C,
+    ) -> Option<&mut Node<K, V, A>>
+    where
+        K: Borrow<Q>,
+    {
+        match cmp.compare(key, self.key.borrow()) {
+            Ordering::Equal => Some(self),
+            Ordering::Less => self.left.as_mut().and_then(|left| left.find_mut(key, cmp)),
+            Ordering::Greater => self
+                .right
+                .as_mut()
+                .and_then(|right| right.find_mut(key, cmp)),
+        }
+    }
+    /// Deletes `key` from the (possibly empty) tree rooted at `tree`.
+    ///
+    /// Returns the removed entry, if there was one.
+    fn delete_from<Q: ?Sized, C: Comparator<Q>>(
+        tree: &mut Link<K, V, A>,
+        key: &Q,
+        cmp: &C,
+    ) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+    {
+        let mut zipper = Zipper::new(tree);
+        loop {
+            let node = zipper.focus.as_ref()?;
+            let step = cmp.compare(key, node.key.borrow());
+            if step == Ordering::Equal {
+                break;
+            }
+            zipper.descend(step);
+        }
+        Some(zipper.remove_focus().0)
+    }
+    /// Unlinks the node with the smallest key below `link`, which must not be
+    /// empty. Returns it and whether the height of the subtree at `link`
+    /// changed.
+    fn detach_first(link: &mut Link<K, V, A>) -> (Box<Node<K, V, A>>, bool) {
+        Node::detach_outermost(link, Ordering::Less)
+    }
+    /// Unlinks the node with the greatest key below `link`, which must not be
+    /// empty. Returns it and whether the height of the subtree at `link`
+    /// changed.
+    fn detach_last(link: &mut Link<K, V, A>) -> (Box<Node<K, V, A>>, bool) {
+        Node::detach_outermost(link, Ordering::Greater)
+    }
+    /// Unlinks the node at the end of the `side` spine below `link`, putting
+    /// its other child in its place.
+    fn detach_outermost(link: &mut Link<K, V, A>, side: Ordering) -> (Box<Node<K, V, A>>, bool) {
+        let mut zipper = Zipper::new(link);
+        while zipper
+            .focus
+            .as_ref()
+            .expect("detached link should hold a node")
+            .child(side)
+            .is_some()
+        {
+            zipper.descend(side);
+        }
+        let mut outermost = zipper.focus.take().expect("loop stopped at a node");
+        zipper.focus = outermost.child_link(side.reverse()).take();
+        (outermost, zipper.zip(true))
+    }
+    /// Combines the summaries of the entries whose keys are within `range`.
+    fn fold_range<Q: ?Sized, R: RangeBounds<Q>, C: Comparator<Q>>(
+        &self,
+        range: &R,
+        cmp: &C,
+    ) -> Option<A>
+    where
+        K: Borrow<Q>,
+    {
+        let key = self.key.borrow();
+        if below_range(key, range, cmp) {
+            self.right.as_ref()?.fold_range(range, cmp)
+        } else if above_range(key, range, cmp) {
+            self.left.as_ref()?.fold_range(range, cmp)
+        } else {
+            let own = A::summarize(&self.key, &self.value);
+            let summary = match self
+                .left
+                .as_ref()
+                .and_then(|left| left.fold_from(range, cmp))
+            {
+                Some(left) => left.combine(&own),
+                None => own,
+            };
+            Some(
+                match self
+                    .right
+                    .as_ref()
+                    .and_then(|right| right.fold_to(range, cmp))
+                {
+                    Some(right) => summary.combine(&right),
+                    None => summary,
+                },
+            )
+        }
+    }
+    /// Folds the keys not below `range`, knowing that none is above it.
+    fn fold_from<Q: ?Sized, R: RangeBounds<Q>, C: Comparator<Q>>(
+        &self,
+        range: &R,
+        cmp: &C,
+    ) -> Option<A>
+    where
+        K: Borrow<Q>,
+    {
+        if below_range(self.key.borrow(), range, cmp) {
+            return self.right.as_ref()?.fold_from(range, cmp);
+        }
+        let own = A::summarize(&self.key, &self.value);
+        let summary = match self
+            .left
+            .as_ref()
+            .and_then(|left| left.fold_from(range, cmp))
+        {
+            Some(left) => left.combine(&own),
+            None => own,
+        };
+        Some(match &self.right {
+            Some(right) => summary.combine(&right.summary),
+            None => summary,
+        })
+    }
+    /// Folds the keys not above `range`, knowing that none is below it.
+    fn fold_to<Q: ?Sized, R: RangeBounds<Q>, C: Comparator<Q>>(
+        &self,
+        range: &R,
+        cmp: &C,
+    ) -> Option<A>
+    where
+        K: Borrow<Q>,
+    {
+        if above_range(self.key.borrow(), range, cmp) {
+            return self.left.as_ref()?.fold_to(range, cmp);
+        }
+        let own = A::summarize(&self.key, &self.value);
+        let summary = match &self.left {
+            Some(left) => left.summary.combine(&own),
+            None => own,
+        };
+        Some(
+            match self
+                .right
+                .as_ref()
+                .and_then(|right| right.fold_to(range, cmp))
+            {
+                Some(right) => summary.combine(&right),
+                None => summary,
+            },
+        )
+    }
+    fn leftmost(&self) -> &Node<K, V, A> {
+        self.left.as_ref().map_or(self, |left| left.leftmost())
+    }
+    fn rightmost(&self) -> &Node<K, V, A> {
+        self.right.as_ref().map_or(self, |right| right.rightmost())
+    }
+    /// Deletes the smallest entry of the (possibly empty) tree rooted at `tree`.
+    fn delete_first(tree: &mut Link<K, V, A>) -> Option<(K, V)> {
+        tree.as_ref()?;
+        let Node { key, value, .. } = *Node::detach_first(tree).0;
+        Some((key, value))
+    }
+    /// Deletes the greatest entry of the (possibly empty) tree rooted at `tree`.
+    fn delete_last(tree: &mut Link<K, V, A>) -> Option<(K, V)> {
+        tree.as_ref()?;
+        let Node { key, value, .. } = *Node::detach_last(tree).0;
+        Some((key, value))
+    }
+    /// Builds a perfectly balanced tree from the next `len` entries, which
+    /// must be sorted by key.
+    fn from_sorted(entries: &mut impl Iterator<Item = (K, V)>, len: usize) -> Link<K, V, A> {
+        if len == 0 {
+            return None;
+        }
+        let left = Node::from_sorted(entries, len / 2);
+        let (key, value) = entries.next().expect("fewer entries than expected");
+        let right = Node::from_sorted(entries, len - len / 2 - 1);
+        Node::new_entry(key, value, left, right)
+    }
+    /// Builds a balanced tree from entries in any order.
+    ///
+    /// Entries that are not yet strictly increasing are sorted first, which
+    /// takes advantage of sorted runs. Of several entries with equal keys the
+    /// first one is kept, with the value of the last one if `keep_last`.
+    fn from_unsorted<C: Comparator<K>>(
+        mut entries: Vec<(K, V)>,
+        cmp: &C,
+        keep_last: bool,
+    ) -> Link<K, V, A> {
+        let sorted = entries
+            .windows(2)
+            .all(|pair| cmp.compare(&pair[0].0, &pair[1].0) == Ordering::Less);
+        if !sorted {
+            entries.sort_by(|a, b| cmp.compare(&a.0, &b.0));
+            entries.dedup_by(|later, earlier| {
+                let equal = cmp.compare(&later.0, &earlier.0) == Ordering::Equal;
+                if equal && keep_last {
+                    mem::swap(&mut later.1, &mut earlier.1);
+                }
+                equal
+            });
+        }
+        let len = entries.len();
+        Node::from_sorted(&mut entries.into_iter(), len)
+    }
+    /// Joins `left`, the entry `key` and `right` into one balanced tree.
+    ///
+    /// All keys of `left` must be smaller than `key` and all keys of `right`
+    /// greater. Takes time proportional to the difference of their heights.
+    fn join(left: Link<K, V, A>, key: K, value: V, right: Link<K, V, A>) -> Link<K, V, A> {
+        let (left_height, right_height) = (Node::height_of(&left), Node::height_of(&right));
+        match (left, right) {
+            (Some(left), right) if left_height > right_height + 1 => {
+                left.join_right(key, value, right)
+            }
+            (left, Some(right)) if right_height > left_height + 1 => {
+                right.join_left(left, key, value)
+            }
+            (left, right) => Node::new_entry(key, value, left, right),
+        }
+    }
+    /// Joins along the right spine of the taller `left`.
+    fn join_right(mut self: Box<Self>, key: K, value: V, right: Link<K, V, A>) -> Link<K, V, A> {
+        self.right = match self.right.take() {
+            Some(inner) if inner.height() + 1 > Node::height_of(&right) + 1 => {
+                inner.join_right(key, value, right)
+            }
+            inner => Node::new_entry(key, value, inner, right),
+        };
+        let mut link = Some(self);
+        Node::rebalance(&mut link);
+        link
+    }
+    /// Joins along the left spine of the taller `right`.
+    fn join_left(mut self: Box<Self>, left: Link<K, V, A>, key: K, value: V) -> Link<K, V, A> {
+        self.left = match self.left.take() {
+            Some(inner) if inner.height() + 1 > Node::height_of(&left) + 1 => {
+                inner.join_left(left, key, value)
+            }
+            inner => Node::new_entry(key, value, left, inner),
+        };
+        let mut link = Some(self);
+        Node::rebalance(&mut link);
+        link
+    }
+    /// Concatenates two trees, all keys of `left` being smaller than all keys
+    /// of `right`.
+    fn concat(mut left: Link<K, V, A>, right: Link<K, V, A>) -> Link<K, V, A> {
+        match Node::delete_last(&mut left) {
+            Some((key, value)) => Node::join(left, key, value, right),
+            None => right,
+        }
+    }
+    /// Splits the tree rooted at `tree` into the entries with keys below
+    /// `key`, the entry equal to `key` if there is one, and the entries with
+    /// keys above `key`.
+    fn split<Q: ?Sized, C: Comparator<Q>>(tree: Link<K, V, A>, key: &Q, cmp: &C) -> Split<K, V, A>
+    where
+        K: Borrow<Q>,
+    {
+        let node = match tree {
+            None => return (None, None, None),
+            Some(node) => *node,
+        };
+        let Node {
+            key: node_key,
+            value,
+            left,
+            right,
+            ..
+        } = node;
+        match cmp.compare(key, node_key.borrow()) {
+            Ordering::Equal => (left, Some((node_key, value)), right),
+            Ordering::Less => {
+                let (below, found, above) = Node::split(left, key, cmp);
+                (below, found, Node::join(above, node_key, value, right))
+            }
+            Ordering::Greater => {
+                let (below, found, above) = Node::split(right, key, cmp);
+                (Node::join(left, node_key, value, below), found, above)
+            }
+        }
+    }
+    /// Keeps the entries for which `f` returns `true`, joining the kept parts
+    /// of each subtree back together.
+    fn retain<F: FnMut(&K, &mut V) -> bool>(tree: Link<K, V, A>, f: &mut F) -> Link<K, V, A> {
+        let node = match tree {
+            None => return None,
+            Some(node) => *node,
+        };
+        let Node {
+            key,
+            mut value,
+            left,
+            right,
+            ..
+        } = node;
+        let left = Node::retain(left, f);
+        let keep = f(&key, &mut value);
+        let right = Node::retain(right, f);
+        if keep {
+            Node::join(left, key, value, right)
+        } else {
+            Node::concat(left, right)
+        }
+    }
+    /// Merges the trees rooted at `tree` and `other`, keeping the entries of
+    /// `other` for keys present in both.
+    ///
+    /// Concatenates in O(log n) when all keys of one tree are smaller than
+    /// all keys of the other, and takes the union otherwise.
+    fn append<C: Comparator<K>>(
+        tree: Link<K, V, A>,
+        other: Link<K, V, A>,
+        cmp: &C,
+    ) -> Link<K, V, A> {
+        let precedes = |left: &Link<K, V, A>, right: &Link<K, V, A>| match (left, right) {
+            (Some(left), Some(right)) => {
+                cmp.compare(&left.rightmost().key, &right.leftmost().key) == Ordering::Less
+            }
+            _ => true,
+        };
+        if precedes(&tree, &other) {
+            Node::concat(tree, other)
+        } else if precedes(&other, &tree) {
+            Node::concat(other, tree)
+        } else {
+            Node::union(other, tree, cmp)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let bst = BST::new();
+        assert_eq!(bst, BST::new());
+        assert_ne!(bst.root, Node::new_node(11, None, None));
+    }
+
+    #[test]
+    fn root_eq() {
+        let mut bst: BST<i32> = BST::new();
+        bst.insert(10);
+        assert_eq!(bst.root, Node::new_node(10, None, None));
+        assert_ne!(bst.root, Node::new_node(11, None, None));
+        assert_ne!(
+            bst.root,
+            Node::new_node(11, Node::new_node(10, None, None), None)
+        );
+    }
+
+    #[test]
+    fn insert_simple() {
+        let mut bst: BST<i32> = BST::new();
+        bst.insert(10);
+        assert_eq!(bst.root, Node::new_node(10, None, None,));
+        bst.insert(20);
         assert_eq!(
-            bst,
-            BST {
-                root: Node::new_node(10, None, Node::new_node(20, None, None,),)
-            }
+            bst.root,
+            Node::new_node(10, None, Node::new_node(20, None, None,),)
         );
         bst.insert(30);
         assert_eq!(
-            bst,
-            BST {
-                root: Node::new_node(
-                    20,
-                    Node::new_node(10, None, None,),
-                    Node::new_node(30, None, None,),
-                )
-            }
+            bst.root,
+            Node::new_node(
+                20,
+                Node::new_node(10, None, None,),
+                Node::new_node(30, None, None,),
+            )
         );
         bst.insert(40);
         assert_eq!(
-            bst,
-            BST {
-                root: Node::new_node(
-                    20,
-                    Node::new_node(10, None, None,),
-                    Node::new_node(30, None, Node::new_node(40, None, None,)),
-                )
-            }
+            bst.root,
+            Node::new_node(
+                20,
+                Node::new_node(10, None, None,),
+                Node::new_node(30, None, Node::new_node(40, None, None,)),
+            )
         )
     }
 
@@ -434,26 +2245,24 @@ mod tests {
         bst.insert(12);
         bst.insert(10);
         assert_eq!(
-            bst,
-            BST {
-                root: Node::new_node(
-                    12,
-                    Node::new_node(
-                        10,
-                        Node::new_node(5, None, None),
-                        Node::new_node(11, None, None),
-                    ),
+            bst.root,
+            Node::new_node(
+                12,
+                Node::new_node(
+                    10,
+                    Node::new_node(5, None, None),
+                    Node::new_node(11, None, None),
+                ),
+                Node::new_node(
+                    15,
+                    Node::new_node(13, None, None,),
                     Node::new_node(
-                        15,
-                        Node::new_node(13, None, None,),
-                        Node::new_node(
-                            18,
-                            Node::new_node(17, None, None),
-                            Node::new_node(19, None, None),
-                        ),
+                        18,
+                        Node::new_node(17, None, None),
+                        Node::new_node(19, None, None),
                     ),
-                )
-            }
+                ),
+            )
         );
     }
 
@@ -470,27 +2279,25 @@ mod tests {
         bst.insert(17);
         bst.insert(19);
 
-        bst.delete(10);
-        bst.delete(11);
-        bst.delete(5);
-        bst.delete(18);
+        bst.remove(&10);
+        bst.remove(&11);
+        bst.remove(&5);
+        bst.remove(&18);
         assert_eq!(
-            bst,
-            BST {
-                root: Node::new_node(
-                    15,
-                    Node::new_node(12, None, Node::new_node(13, None, None,),),
-                    Node::new_node(19, Node::new_node(17, None, None,), None,),
-                )
-            }
+            bst.root,
+            Node::new_node(
+                15,
+                Node::new_node(12, None, Node::new_node(13, None, None,),),
+                Node::new_node(19, Node::new_node(17, None, None,), None,),
+            )
         );
 
-        bst.delete(12);
-        bst.delete(15);
-        bst.delete(19);
-        bst.delete(17);
-        bst.delete(13);
-        assert_eq!(bst, BST { root: None });
+        bst.remove(&12);
+        bst.remove(&15);
+        bst.remove(&19);
+        bst.remove(&17);
+        bst.remove(&13);
+        assert_eq!(bst, BST::new());
     }
 
     #[test]
@@ -503,23 +2310,24 @@ mod tests {
         bst.insert(7);
 
         assert_eq!(
-            bst,
-            BST {
-                root: Node::new_node(
-                    10,
-                    Node::new_node(
-                        8,
-                        Node::new_node(7, None, None),
-                        Node::new_node(9, None, None),
-                    ),
-                    Node::new_node(20, None, None,),
-                )
-            }
+            bst.root,
+            Node::new_node(
+                10,
+                Node::new_node(
+                    8,
+                    Node::new_node(7, None, None),
+                    Node::new_node(9, None, None),
+                ),
+                Node::new_node(20, None, None,),
+            )
         );
 
-        assert_eq!(bst.root.as_ref().unwrap().height, 2);
-        assert_eq!(bst.root.as_ref().unwrap().balance_factor, -1);
-        assert_eq!(bst.root.as_ref().unwrap().left.as_ref().unwrap().height, 1);
+        assert_eq!(bst.root.as_ref().unwrap().height(), 2);
+        assert_eq!(bst.root.as_ref().unwrap().balance_factor(), -1);
+        assert_eq!(
+            bst.root.as_ref().unwrap().left.as_ref().unwrap().height(),
+            1
+        );
         assert_eq!(
             bst.root
                 .as_ref()
@@ -527,7 +2335,7 @@ mod tests {
                 .left
                 .as_ref()
                 .unwrap()
-                .balance_factor,
+                .balance_factor(),
             0
         );
         assert_eq!(
@@ -540,7 +2348,7 @@ mod tests {
                 .left
                 .as_ref()
                 .unwrap()
-                .height,
+                .height(),
             0
         );
         assert_eq!(
@@ -553,7 +2361,7 @@ mod tests {
                 .left
                 .as_ref()
                 .unwrap()
-                .balance_factor,
+                .balance_factor(),
             0
         );
         assert_eq!(
@@ -566,7 +2374,7 @@ mod tests {
                 .right
                 .as_ref()
                 .unwrap()
-                .height,
+                .height(),
             0
         );
         assert_eq!(
@@ -579,10 +2387,13 @@ mod tests {
                 .right
                 .as_ref()
                 .unwrap()
-                .balance_factor,
+                .balance_factor(),
+            0
+        );
+        assert_eq!(
+            bst.root.as_ref().unwrap().right.as_ref().unwrap().height(),
             0
         );
-        assert_eq!(bst.root.as_ref().unwrap().right.as_ref().unwrap().height, 0);
         assert_eq!(
             bst.root
                 .as_ref()
@@ -590,26 +2401,27 @@ mod tests {
                 .right
                 .as_ref()
                 .unwrap()
-                .balance_factor,
+                .balance_factor(),
             0
         );
 
-        bst.delete(10);
+        bst.remove(&10);
 
         assert_eq!(
-            bst,
-            BST {
-                root: Node::new_node(
-                    8,
-                    Node::new_node(7, None, None,),
-                    Node::new_node(20, Node::new_node(9, None, None), None),
-                )
-            }
+            bst.root,
+            Node::new_node(
+                8,
+                Node::new_node(7, None, None,),
+                Node::new_node(20, Node::new_node(9, None, None), None),
+            )
         );
 
-        assert_eq!(bst.root.as_ref().unwrap().height, 2);
-        assert_eq!(bst.root.as_ref().unwrap().balance_factor, 1);
-        assert_eq!(bst.root.as_ref().unwrap().left.as_ref().unwrap().height, 0);
+        assert_eq!(bst.root.as_ref().unwrap().height(), 2);
+        assert_eq!(bst.root.as_ref().unwrap().balance_factor(), 1);
+        assert_eq!(
+            bst.root.as_ref().unwrap().left.as_ref().unwrap().height(),
+            0
+        );
         assert_eq!(
             bst.root
                 .as_ref()
@@ -617,10 +2429,13 @@ mod tests {
                 .left
                 .as_ref()
                 .unwrap()
-                .balance_factor,
+                .balance_factor(),
             0
         );
-        assert_eq!(bst.root.as_ref().unwrap().right.as_ref().unwrap().height, 1);
+        assert_eq!(
+            bst.root.as_ref().unwrap().right.as_ref().unwrap().height(),
+            1
+        );
         assert_eq!(
             bst.root
                 .as_ref()
@@ -628,7 +2443,7 @@ mod tests {
                 .right
                 .as_ref()
                 .unwrap()
-                .balance_factor,
+                .balance_factor(),
             -1
         );
         assert_eq!(
@@ -641,258 +2456,1111 @@ mod tests {
                 .left
                 .as_ref()
                 .unwrap()
-                .height,
+                .height(),
+            0
+        );
+        assert_eq!(
+            bst.root
+                .as_ref()
+                .unwrap()
+                .right
+                .as_ref()
+                .unwrap()
+                .left
+                .as_ref()
+                .unwrap()
+                .balance_factor(),
             0
         );
+    }
+
+    #[test]
+    fn contains_simple() {
+        let mut bst: BST<i32> = BST::new();
+        bst.insert(10);
+        bst.insert(5);
+        bst.insert(10);
+        bst.insert(7);
+
+        assert!(bst.contains(&10));
+        assert!(bst.contains(&5));
+        assert!(bst.contains(&7));
+        assert!(!bst.contains(&1313));
+    }
+
+    #[test]
+    fn contains() {
+        let mut bst: BST<i32> = BST::new();
+        bst.insert(10);
+        bst.insert(5);
+        bst.insert(10);
+        bst.insert(7);
+
+        assert!(bst.contains(&10));
+        assert!(bst.contains(&5));
+        assert!(bst.contains(&7));
+        assert!(!bst.contains(&1313));
+
+        bst.remove(&10);
+        bst.remove(&7);
+        bst.remove(&1313);
+
+        assert!(!bst.contains(&10));
+        assert!(bst.contains(&5));
+        assert!(!bst.contains(&7));
+        assert!(!bst.contains(&1313));
+        assert!(!bst.contains(&1414));
+    }
+
+    #[test]
+    fn iter() {
+        let mut bst: BST<i32> = BST::new();
+        bst.insert(10);
+        bst.insert(5);
+        bst.insert(15);
+        bst.insert(12);
+        bst.insert(18);
+        bst.insert(11);
+        bst.insert(13);
+        bst.insert(17);
+        bst.insert(19);
+        bst.insert(15);
+        bst.insert(12);
+        bst.insert(10);
+
+        let sorted_values: Vec<i32> = vec![5, 10, 11, 12, 13, 15, 17, 18, 19];
+        assert_eq!(
+            sorted_values,
+            (&bst).into_iter().copied().collect::<Vec<i32>>()
+        );
+
+        bst.remove(&10);
+        let sorted_values: Vec<i32> = vec![5, 11, 12, 13, 15, 17, 18, 19];
+        assert_eq!(
+            sorted_values,
+            (&bst).into_iter().copied().collect::<Vec<i32>>()
+        );
+
+        bst.insert(14);
+        let sorted_values: Vec<i32> = vec![5, 11, 12, 13, 14, 15, 17, 18, 19];
+        assert_eq!(
+            sorted_values,
+            (&bst).into_iter().copied().collect::<Vec<i32>>()
+        );
+        assert_eq!(sorted_values, bst.into_iter().collect::<Vec<i32>>());
+    }
+
+    #[test]
+    fn left_rotation_of_root() {
+        let mut bst: BST<i32> = BST::new();
+        bst.insert(100);
+        bst.insert(50);
+        bst.insert(200);
+        bst.insert(25);
+        bst.insert(75);
+        bst.insert(150);
+        bst.insert(250);
+        bst.insert(120);
+        bst.insert(170);
+        bst.insert(220);
+        bst.insert(270);
+        bst.insert(210);
+        bst.insert(230);
+        bst.insert(260);
+        bst.insert(280);
+
+        assert_eq!(
+            bst.root,
+            Node::new_node(
+                200,
+                Node::new_node(
+                    100,
+                    Node::new_node(
+                        50,
+                        Node::new_node(25, None, None,),
+                        Node::new_node(75, None, None,),
+                    ),
+                    Node::new_node(
+                        150,
+                        Node::new_node(120, None, None,),
+                        Node::new_node(170, None, None,),
+                    )
+                ),
+                Node::new_node(
+                    250,
+                    Node::new_node(
+                        220,
+                        Node::new_node(210, None, None,),
+                        Node::new_node(230, None, None,),
+                    ),
+                    Node::new_node(
+                        270,
+                        Node::new_node(260, None, None,),
+                        Node::new_node(280, None, None,),
+                    ),
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn right_rotation_of_root() {
+        let mut bst: BST<i32> = BST::new();
+        bst.insert(100);
+        bst.insert(50);
+        bst.insert(20);
+        assert_eq!(
+            bst.root,
+            Node::new_node(
+                50,
+                Node::new_node(20, None, None),
+                Node::new_node(100, None, None)
+            )
+        );
+    }
+
+    #[test]
+    fn rotate_in_get_leftmost() {
+        let mut bst: BST<i32> = BST::new();
+        bst.insert(3);
+        bst.insert(1);
+        bst.insert(5);
+        bst.insert(2);
+        bst.insert(4);
+        bst.insert(6);
+        bst.insert(7);
+
+        bst.remove(&1);
+
+        assert_eq!(
+            bst.root,
+            Node::new_node(
+                5,
+                Node::new_node(
+                    3,
+                    Node::new_node(2, None, None,),
+                    Node::new_node(4, None, None,),
+                ),
+                Node::new_node(6, None, Node::new_node(7, None, None,),)
+            )
+        );
+    }
+
+    #[test]
+    fn test_rotation_of_right_in_delete_by_node() {
+        let mut bst: BST<i32> = BST::new();
+        bst.insert(5);
+        bst.insert(3);
+        bst.insert(7);
+        bst.insert(6);
+        bst.insert(2);
+        bst.insert(4);
+        bst.insert(9);
+        bst.insert(8);
+        bst.insert(10);
+        bst.insert(1);
+
+        assert_eq!(
+            bst.root,
+            Node::new_node(
+                5,
+                Node::new_node(
+                    3,
+                    Node::new_node(2, Node::new_node(1, None, None), None),
+                    Node::new_node(4, None, None),
+                ),
+                Node::new_node(
+                    7,
+                    Node::new_node(6, None, None),
+                    Node::new_node(
+                        9,
+                        Node::new_node(8, None, None),
+                        Node::new_node(10, None, None)
+                    )
+                )
+            )
+        );
+
+        bst.remove(&5);
+
+        assert_eq!(
+            bst.root,
+            Node::new_node(
+                6,
+                Node::new_node(
+                    3,
+                    Node::new_node(2, Node::new_node(1, None, None), None),
+                    Node::new_node(4, None, None),
+                ),
+                Node::new_node(
+                    9,
+                    Node::new_node(7, None, Node::new_node(8, None, None)),
+                    Node::new_node(10, None, None)
+                )
+            )
+        );
+    }
+    pub(crate) fn assert_balanced<K: Ord, V>(node: &Option<Box<Node<K, V>>>) -> i64 {
+        match node {
+            None => -1,
+            Some(node) => {
+                let left = assert_balanced(&node.left);
+                let right = assert_balanced(&node.right);
+                assert_eq!(node.height() as i64, max(left, right) + 1);
+                assert_eq!(node.balance_factor() as i64, right - left);
+                assert!((right - left).abs() <= 1);
+                assert_eq!(
+                    node.size(),
+                    1 + Node::size_of(&node.left) + Node::size_of(&node.right)
+                );
+                max(left, right) + 1
+            }
+        }
+    }
+
+    #[test]
+    fn delete_keeps_heights() {
+        let mut bst: BST<i32> = BST::new();
+        for value in 0..64 {
+            bst.insert(value);
+        }
+        for value in (0..64).step_by(3) {
+            bst.remove(&value);
+            assert_balanced(&bst.root);
+        }
+        for value in (0..64).rev() {
+            bst.remove(&value);
+            assert_balanced(&bst.root);
+        }
+        assert_eq!(bst, BST::new());
+    }
+
+    #[test]
+    fn delete_missing_keeps_subtree() {
+        let mut bst: BST<i32> = BST::new();
+        bst.insert(10);
+        bst.insert(20);
+        bst.remove(&5);
+        assert_eq!(
+            bst.root,
+            Node::new_node(10, None, Node::new_node(20, None, None))
+        );
+    }
+
+    #[test]
+    fn entry() {
+        let mut bst: BST<i32> = BST::new();
+        assert_eq!(bst.entry(100).or_insert(), &100);
+        bst.entry(50).or_insert();
+        assert_eq!(bst.entry(100).or_insert(), &100);
+        match bst.entry(20) {
+            Entry::Occupied(_) => panic!("20 should be missing"),
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.get(), &20);
+                assert_eq!(entry.insert(), &20);
+            }
+        }
+        assert_eq!(
+            bst.root,
+            Node::new_node(
+                50,
+                Node::new_node(20, None, None),
+                Node::new_node(100, None, None)
+            )
+        );
+
+        match bst.entry(50) {
+            Entry::Occupied(entry) => {
+                assert_eq!(entry.get(), &50);
+                assert_eq!(entry.remove(), 50);
+            }
+            Entry::Vacant(_) => panic!("50 should be present"),
+        }
+        assert!(!bst.contains(&50));
+        assert_eq!(bst.entry(7).get(), &7);
+        match bst.entry(7) {
+            Entry::Occupied(_) => panic!("7 should be missing"),
+            Entry::Vacant(entry) => assert_eq!(entry.into_value(), 7),
+        }
+        assert_eq!(bst.iter().copied().collect::<Vec<i32>>(), vec![20, 100]);
+    }
+
+    #[test]
+    fn entry_insert_rebalances() {
+        let mut bst: BST<i32> = BST::new();
+        for value in [30, 20] {
+            bst.insert(value);
+        }
+        // Inserting 10 rotates it to the root's left, away from its leaf.
+        let entry = match bst.entry(10) {
+            Entry::Occupied(_) => panic!("10 should be missing"),
+            Entry::Vacant(entry) => entry.insert_entry(),
+        };
+        assert_eq!(entry.get(), &10);
+        assert_eq!(entry.remove(), 10);
+        assert_eq!(bst.iter().copied().collect::<Vec<i32>>(), vec![20, 30]);
+        assert_balanced(&bst.root);
+    }
+
+    #[test]
+    fn borrowed_lookups() {
+        let mut bst: BST<String> = BST::new();
+        bst.insert("bbb".to_string());
+        bst.insert("aaa".to_string());
+        bst.insert("ccc".to_string());
+
+        assert!(bst.contains("aaa"));
+        assert!(!bst.contains("ddd"));
+        assert_eq!(bst.get("ccc"), Some(&"ccc".to_string()));
+        assert_eq!(bst.get("ddd"), None);
+
+        assert!(bst.remove("bbb"));
+        assert!(!bst.remove("bbb"));
+        assert_eq!(
+            bst.iter().map(String::as_str).collect::<Vec<&str>>(),
+            vec!["aaa", "ccc"]
+        );
+    }
+
+    #[test]
+    fn mutation_results() {
+        let mut bst: BST<i32> = BST::new();
+        assert!(bst.insert(10));
+        assert!(bst.insert(5));
+        assert!(!bst.insert(10));
+        assert_eq!(bst.take(&5), Some(5));
+        assert_eq!(bst.take(&5), None);
+        assert!(!bst.remove(&5));
+        assert!(bst.remove(&10));
+        assert_eq!(bst, BST::new());
+    }
+
+    #[test]
+    fn replace() {
+        // Equal under `Ord`, but still distinguishable.
+        #[derive(Debug)]
+        struct Tagged(i32, &'static str);
+        impl PartialEq for Tagged {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0
+            }
+        }
+        impl Eq for Tagged {}
+        impl PartialOrd for Tagged {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Tagged {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.0.cmp(&other.0)
+            }
+        }
+
+        let mut bst: BST<Tagged> = BST::new();
+        assert!(bst.replace(Tagged(2, "old")).is_none());
+        assert!(bst.replace(Tagged(1, "old")).is_none());
+        assert!(bst.replace(Tagged(3, "old")).is_none());
+        assert!(!bst.insert(Tagged(2, "ignored")));
+        assert_eq!(bst.replace(Tagged(2, "new")).map(|t| t.1), Some("old"));
         assert_eq!(
-            bst.root
-                .as_ref()
-                .unwrap()
-                .right
-                .as_ref()
-                .unwrap()
-                .left
-                .as_ref()
-                .unwrap()
-                .balance_factor,
-            0
+            bst.iter().map(|t| t.1).collect::<Vec<&str>>(),
+            vec!["old", "new", "old"]
         );
+        assert_eq!(bst.take(&Tagged(2, "")).map(|t| t.1), Some("new"));
     }
 
     #[test]
-    fn contains_simple() {
-        let mut bst: BST<i32> = BST::new();
-        bst.insert(10);
-        bst.insert(5);
-        bst.insert(10);
-        bst.insert(7);
-
-        assert!(bst.contains(10));
-        assert!(bst.contains(5));
-        assert!(bst.contains(7));
-        assert!(!bst.contains(1313));
+    fn panicking_comparator_keeps_tree() {
+        // Panics only next to 13, which is deep in the tree, after the
+        // descent has taken part of it apart.
+        let mut bst = BST::with_comparator(|a: &i32, b: &i32| {
+            assert!(*a != 13 || b.abs_diff(13) != 1, "comparator panicked");
+            a.cmp(b)
+        });
+        let values: Vec<i32> = (0..40).filter(|value| *value != 13).collect();
+        bst.extend(values.iter().copied());
+        let unwound = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| bst.insert(13)));
+        assert!(unwound.is_err());
+        let unwound = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| bst.remove(&13)));
+        assert!(unwound.is_err());
+        assert_eq!(bst.len(), values.len());
+        assert_eq!(bst.iter().copied().collect::<Vec<i32>>(), values);
+        assert_balanced(&bst.root);
     }
 
     #[test]
-    fn contains() {
-        let mut bst: BST<i32> = BST::new();
-        bst.insert(10);
-        bst.insert(5);
-        bst.insert(10);
-        bst.insert(7);
+    fn zero_sized_comparator() {
+        #[derive(Default)]
+        struct Reverse;
+        impl Comparator<i32> for Reverse {
+            fn compare(&self, a: &i32, b: &i32) -> Ordering {
+                b.cmp(a)
+            }
+        }
 
-        assert!(bst.contains(10));
-        assert!(bst.contains(5));
-        assert!(bst.contains(7));
-        assert!(!bst.contains(1313));
+        let mut bst: BST<i32, Reverse> = BST::default();
+        for value in [3, 1, 4, 1, 5, 9, 2, 6] {
+            bst.insert(value);
+        }
+        assert_eq!(
+            bst.iter().copied().collect::<Vec<i32>>(),
+            vec![9, 6, 5, 4, 3, 2, 1]
+        );
+        assert!(bst.contains(&4));
+        assert!(bst.remove(&4));
+        assert!(!bst.contains(&4));
+    }
 
-        bst.delete(10);
-        bst.delete(7);
-        bst.delete(1313);
+    #[test]
+    fn runtime_comparator() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        enum Column {
+            Name,
+            Priority,
+        }
+        struct Record {
+            name: &'static str,
+            priority: u8,
+        }
+        let records =
+            || [("b", 1), ("c", 3), ("a", 2)].map(|(name, priority)| Record { name, priority });
 
-        assert!(!bst.contains(10));
-        assert!(bst.contains(5));
-        assert!(!bst.contains(7));
-        assert!(!bst.contains(1313));
-        assert!(!bst.contains(1414));
+        for (column, expected) in [
+            (Column::Name, ["a", "b", "c"]),
+            (Column::Priority, ["b", "a", "c"]),
+        ] {
+            let mut bst = BST::with_comparator(move |a: &Record, b: &Record| match column {
+                Column::Name => a.name.cmp(b.name),
+                Column::Priority => a.priority.cmp(&b.priority),
+            });
+            for record in records() {
+                bst.insert(record);
+            }
+            assert_eq!(
+                bst.iter().map(|record| record.name).collect::<Vec<&str>>(),
+                expected
+            );
+            assert!(bst.contains(&Record {
+                name: "c",
+                priority: 3
+            }));
+        }
     }
 
     #[test]
-    fn iter() {
+    fn range() {
         let mut bst: BST<i32> = BST::new();
-        bst.insert(10);
-        bst.insert(5);
-        bst.insert(15);
-        bst.insert(12);
-        bst.insert(18);
-        bst.insert(11);
-        bst.insert(13);
-        bst.insert(17);
-        bst.insert(19);
-        bst.insert(15);
-        bst.insert(12);
-        bst.insert(10);
+        for value in (0..100).step_by(5) {
+            bst.insert(value);
+        }
+        let range = |r: Range<i32>| r.copied().collect::<Vec<i32>>();
 
-        let sorted_values: Vec<i32> = vec![5, 10, 11, 12, 13, 15, 17, 18, 19];
+        assert_eq!(range(bst.range(10..25)), vec![10, 15, 20]);
+        assert_eq!(range(bst.range(11..=25)), vec![15, 20, 25]);
+        assert_eq!(range(bst.range(..12)), vec![0, 5, 10]);
+        assert_eq!(range(bst.range(88..)), vec![90, 95]);
         assert_eq!(
-            sorted_values,
-            (&bst).into_iter().map(|v| *v).collect::<Vec<i32>>()
+            range(bst.range((Bound::Excluded(10), Bound::Excluded(25)))),
+            vec![15, 20]
         );
+        assert_eq!(range(bst.range(11..14)), vec![]);
+        assert_eq!(range(bst.range(100..)), vec![]);
+        assert_eq!(range(bst.range(..0)), vec![]);
+        assert_eq!(range(bst.range(7..7)), vec![]);
+        assert_eq!(range(bst.range(..)).len(), 20);
 
-        bst.delete(10);
-        let sorted_values: Vec<i32> = vec![5, 11, 12, 13, 15, 17, 18, 19];
+        for start in -1..101 {
+            for end in start..101 {
+                assert_eq!(
+                    range(bst.range(start..end)),
+                    bst.iter()
+                        .copied()
+                        .filter(|value| (start..end).contains(value))
+                        .collect::<Vec<i32>>()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn range_borrowed() {
+        let mut bst: BST<String> = BST::new();
+        for value in ["apple", "banana", "cherry", "date"] {
+            bst.insert(value.to_string());
+        }
         assert_eq!(
-            sorted_values,
-            (&bst).into_iter().map(|v| *v).collect::<Vec<i32>>()
+            bst.range::<str, _>((Bound::Included("b"), Bound::Excluded("d")))
+                .map(String::as_str)
+                .collect::<Vec<&str>>(),
+            vec!["banana", "cherry"]
         );
+    }
 
-        bst.insert(14);
-        let sorted_values: Vec<i32> = vec![5, 11, 12, 13, 14, 15, 17, 18, 19];
+    #[test]
+    #[should_panic(expected = "range start is greater than range end")]
+    fn range_start_after_end() {
+        let bst: BST<i32> = BST::new();
+        bst.range((Bound::Included(5), Bound::Excluded(3)));
+    }
+
+    #[test]
+    #[should_panic(expected = "range start and end are equal and excluded")]
+    fn range_equal_excluded() {
+        let bst: BST<i32> = BST::new();
+        bst.range((Bound::Excluded(3), Bound::Excluded(3)));
+    }
+
+    #[test]
+    fn iter_rev() {
+        let mut bst: BST<i32> = BST::new();
+        for value in [10, 5, 15, 12, 18, 11, 13, 17, 19] {
+            bst.insert(value);
+        }
         assert_eq!(
-            sorted_values,
-            (&bst).into_iter().map(|v| *v).collect::<Vec<i32>>()
+            bst.iter().rev().copied().collect::<Vec<i32>>(),
+            vec![19, 18, 17, 15, 13, 12, 11, 10, 5]
+        );
+        assert_eq!(
+            bst.range(11..18).rev().copied().collect::<Vec<i32>>(),
+            vec![17, 15, 13, 12, 11]
+        );
+        assert_eq!(
+            bst.into_iter().rev().collect::<Vec<i32>>(),
+            vec![19, 18, 17, 15, 13, 12, 11, 10, 5]
         );
-        assert_eq!(sorted_values, bst.into_iter().collect::<Vec<i32>>());
     }
 
     #[test]
-    fn left_rotation_of_root() {
+    fn iter_both_ends_meet() {
+        for len in 0..40 {
+            let mut bst: BST<i32> = BST::new();
+            for value in 0..len {
+                bst.insert(value);
+            }
+            let mut iter = bst.iter();
+            let mut range = bst.range(1..(len - 1).max(1));
+            let mut front = Vec::new();
+            let mut back = Vec::new();
+            let mut range_values: Vec<i32> = Vec::new();
+            for step in 0.. {
+                let (value, range_value) = if step % 3 == 0 {
+                    (iter.next_back(), range.next_back())
+                } else {
+                    (iter.next(), range.next())
+                };
+                range_values.extend(range_value.copied());
+                match value {
+                    Some(value) if step % 3 == 0 => back.push(*value),
+                    Some(value) => front.push(*value),
+                    None => break,
+                }
+            }
+            assert_eq!(iter.next(), None);
+            assert_eq!(iter.next_back(), None);
+            front.extend(back.iter().rev());
+            assert_eq!(front, (0..len).collect::<Vec<i32>>());
+            range_values.sort();
+            assert_eq!(range_values, (1..len - 1).collect::<Vec<i32>>());
+
+            let mut into_iter = bst.into_iter();
+            let mut values = Vec::new();
+            while let Some(value) = into_iter.next() {
+                values.push(value);
+                if let Some(value) = into_iter.next_back() {
+                    values.push(value);
+                }
+            }
+            values.sort();
+            assert_eq!(values, (0..len).collect::<Vec<i32>>());
+        }
+    }
+
+    #[test]
+    fn first_last() {
         let mut bst: BST<i32> = BST::new();
-        bst.insert(100);
-        bst.insert(50);
-        bst.insert(200);
-        bst.insert(25);
-        bst.insert(75);
-        bst.insert(150);
-        bst.insert(250);
-        bst.insert(120);
-        bst.insert(170);
-        bst.insert(220);
-        bst.insert(270);
-        bst.insert(210);
-        bst.insert(230);
-        bst.insert(260);
-        bst.insert(280);
+        assert_eq!(bst.first(), None);
+        assert_eq!(bst.last(), None);
+        assert_eq!(bst.pop_first(), None);
+        assert_eq!(bst.pop_last(), None);
+        for value in [10, 5, 15, 12, 18, 11, 13, 17, 19] {
+            bst.insert(value);
+        }
+        assert_eq!(bst.first(), Some(&5));
+        assert_eq!(bst.last(), Some(&19));
 
+        assert_eq!(bst.pop_first(), Some(5));
         assert_eq!(
-            bst,
-            BST {
-                root: Node::new_node(
-                    200,
-                    Node::new_node(
-                        100,
-                        Node::new_node(
-                            50,
-                            Node::new_node(25, None, None,),
-                            Node::new_node(75, None, None,),
-                        ),
-                        Node::new_node(
-                            150,
-                            Node::new_node(120, None, None,),
-                            Node::new_node(170, None, None,),
-                        )
-                    ),
+            bst.root,
+            Node::new_node(
+                12,
+                Node::new_node(10, None, Node::new_node(11, None, None)),
+                Node::new_node(
+                    15,
+                    Node::new_node(13, None, None),
                     Node::new_node(
-                        250,
-                        Node::new_node(
-                            220,
-                            Node::new_node(210, None, None,),
-                            Node::new_node(230, None, None,),
-                        ),
-                        Node::new_node(
-                            270,
-                            Node::new_node(260, None, None,),
-                            Node::new_node(280, None, None,),
-                        ),
+                        18,
+                        Node::new_node(17, None, None),
+                        Node::new_node(19, None, None)
                     )
                 )
-            }
+            )
         );
+        assert_eq!(bst.pop_last(), Some(19));
+        assert_eq!(bst.first(), Some(&10));
+        assert_eq!(bst.last(), Some(&18));
     }
 
     #[test]
-    fn right_rotation_of_root() {
+    fn pop_keeps_heights() {
         let mut bst: BST<i32> = BST::new();
-        bst.insert(100);
-        bst.insert(50);
-        bst.insert(20);
+        for value in 0..100 {
+            bst.insert(value);
+        }
+        for value in 0..50 {
+            assert_eq!(bst.pop_first(), Some(value));
+            assert_balanced(&bst.root);
+            assert_eq!(bst.pop_last(), Some(99 - value));
+            assert_balanced(&bst.root);
+        }
+        assert_eq!(bst, BST::new());
+    }
+
+    #[test]
+    fn neighbours() {
+        let mut bst: BST<i32> = BST::new();
+        for value in [10, 20, 30, 40, 50] {
+            bst.insert(value);
+        }
+        assert_eq!(bst.floor(&30), Some(&30));
+        assert_eq!(bst.floor(&35), Some(&30));
+        assert_eq!(bst.floor(&5), None);
+        assert_eq!(bst.ceiling(&30), Some(&30));
+        assert_eq!(bst.ceiling(&35), Some(&40));
+        assert_eq!(bst.ceiling(&55), None);
+        assert_eq!(bst.predecessor(&30), Some(&20));
+        assert_eq!(bst.predecessor(&10), None);
+        assert_eq!(bst.successor(&30), Some(&40));
+        assert_eq!(bst.successor(&50), None);
+        assert_eq!(bst.lower_bound(Bound::Unbounded), Some(&10));
+        assert_eq!(bst.upper_bound(Bound::Unbounded), Some(&50));
+        assert_eq!(bst.lower_bound(Bound::Excluded(&0)), Some(&10));
+        assert_eq!(bst.upper_bound(Bound::Included(&100)), Some(&50));
+
+        for probe in 0..60 {
+            let values: Vec<i32> = bst.iter().copied().collect();
+            assert_eq!(
+                bst.floor(&probe),
+                values.iter().rev().find(|value| **value <= probe)
+            );
+            assert_eq!(
+                bst.successor(&probe),
+                values.iter().find(|value| **value > probe)
+            );
+        }
+        assert_eq!(BST::<i32>::new().ceiling(&1), None);
+    }
+
+    #[test]
+    fn order_statistics() {
+        let mut bst: BST<i32> = BST::new();
+        assert_eq!(bst.nth(0), None);
+        assert_eq!(bst.rank(&5), 0);
+        assert_eq!(bst.count_in_range(..), 0);
+        for value in (0..200).step_by(2).rev() {
+            bst.insert(value);
+            assert_balanced(&bst.root);
+        }
+        for index in 0..100 {
+            assert_eq!(bst.nth(index), Some(&(index as i32 * 2)));
+        }
+        assert_eq!(bst.nth(100), None);
+        assert_eq!(bst.rank(&0), 0);
+        assert_eq!(bst.rank(&7), 4);
+        assert_eq!(bst.rank(&8), 4);
+        assert_eq!(bst.rank(&1000), 100);
+        assert_eq!(bst.count_in_range(..), 100);
+        assert_eq!(bst.count_in_range(10..20), 5);
+        assert_eq!(bst.count_in_range(10..=20), 6);
         assert_eq!(
-            bst,
-            BST {
-                root: Node::new_node(
-                    50,
-                    Node::new_node(20, None, None),
-                    Node::new_node(100, None, None)
-                )
-            }
+            bst.count_in_range((Bound::Excluded(10), Bound::Included(20))),
+            5
         );
+        assert_eq!(bst.count_in_range(..-1), 0);
+        assert_eq!(bst.count_in_range(150..), 25);
+
+        for value in (0..200).step_by(6) {
+            bst.remove(&value);
+            assert_balanced(&bst.root);
+        }
+        let values: Vec<i32> = bst.iter().copied().collect();
+        for (index, value) in values.iter().enumerate() {
+            assert_eq!(bst.nth(index), Some(value));
+            assert_eq!(bst.rank(value), index);
+        }
+        for start in 0..40 {
+            assert_eq!(
+                bst.count_in_range(start..start * 3),
+                bst.range(start..start * 3).count()
+            );
+        }
     }
 
     #[test]
-    fn rotate_in_get_leftmost() {
+    fn len_tracks_changes() {
         let mut bst: BST<i32> = BST::new();
+        assert_eq!(bst.len(), 0);
+        assert!(bst.is_empty());
+        for value in [5, 3, 8, 1, 4] {
+            bst.insert(value);
+        }
+        assert_eq!(bst.len(), 5);
         bst.insert(3);
-        bst.insert(1);
-        bst.insert(5);
-        bst.insert(2);
-        bst.insert(4);
-        bst.insert(6);
-        bst.insert(7);
+        assert_eq!(bst.len(), 5);
+        bst.remove(&3);
+        assert_eq!(bst.len(), 4);
+        bst.remove(&3);
+        assert_eq!(bst.len(), 4);
+        bst.pop_first();
+        bst.pop_last();
+        assert_eq!(bst.len(), 2);
+        assert!(!bst.is_empty());
+    }
 
-        bst.delete(1);
+    #[test]
+    fn exact_size_iterators() {
+        let mut bst: BST<i32> = BST::new();
+        for value in 0..10 {
+            bst.insert(value);
+        }
+        let mut iter = bst.iter();
+        assert_eq!(iter.len(), 10);
+        iter.next();
+        iter.next_back();
+        assert_eq!(iter.size_hint(), (8, Some(8)));
+        assert_eq!(iter.by_ref().count(), 8);
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
 
-        assert_eq!(
-            bst,
-            BST {
-                root: Node::new_node(
-                    5,
-                    Node::new_node(
-                        3,
-                        Node::new_node(2, None, None,),
-                        Node::new_node(4, None, None,),
-                    ),
-                    Node::new_node(6, None, Node::new_node(7, None, None,),)
-                )
+        let mut range = bst.range(2..7);
+        assert_eq!(range.len(), 5);
+        range.next_back();
+        assert_eq!(range.len(), 4);
+        assert_eq!(bst.range(..=3).len(), 4);
+        assert_eq!(bst.range(20..).len(), 0);
+
+        let mut into_iter = bst.into_iter();
+        assert_eq!(into_iter.len(), 10);
+        into_iter.next();
+        assert_eq!(into_iter.len(), 9);
+        into_iter.next_back();
+        assert_eq!(into_iter.size_hint(), (8, Some(8)));
+        assert_eq!(into_iter.count(), 8);
+    }
+
+    /// Keeps all keys of a subtree in order, so any summary that is combined
+    /// in the wrong order or left stale shows up in the comparison.
+    #[derive(Debug, PartialEq)]
+    struct Keys(Vec<i32>);
+
+    impl Augment<i32> for Keys {
+        fn summarize(key: &i32, _: &()) -> Keys {
+            Keys(vec![*key])
+        }
+        fn combine(&self, other: &Keys) -> Keys {
+            Keys([&self.0[..], &other.0[..]].concat())
+        }
+    }
+
+    #[test]
+    fn fold_range() {
+        let mut bst: BST<i32, OrdComparator, Keys> = BST::default();
+        assert_eq!(bst.summary(), None);
+        assert_eq!(bst.fold_range(..), None);
+        for value in [50, 20, 80, 10, 30, 70, 90, 25, 35, 5, 1, 2, 3, 4, 60, 65] {
+            bst.insert(value);
+        }
+        bst.remove(&20);
+        bst.pop_first();
+        bst.pop_last();
+        bst.replace(30);
+        bst.replace(45);
+        bst.entry(40).or_insert();
+
+        let values: Vec<i32> = bst.iter().copied().collect();
+        assert_eq!(bst.summary(), Some(&Keys(values.clone())));
+        for start in 0..100 {
+            for end in start..100 {
+                let expected: Vec<i32> = values
+                    .iter()
+                    .copied()
+                    .filter(|value| (start..end).contains(value))
+                    .collect();
+                let expected = (!expected.is_empty()).then_some(Keys(expected));
+                assert_eq!(bst.fold_range(start..end), expected);
             }
-        );
+        }
+        assert_eq!(bst.fold_range(..=3), Some(Keys(vec![2, 3])));
+        assert_eq!(bst.fold_range(66..), Some(Keys(vec![70, 80])));
     }
 
     #[test]
-    fn test_rotation_of_right_in_delete_by_node() {
+    fn extract_if_keeps_summaries() {
+        let mut bst: BST<i32, OrdComparator, Keys> = (0..60).collect();
+        let extracted: Vec<i32> = bst.extract_if(|value| value % 3 == 0).take(5).collect();
+        assert_eq!(extracted, [0, 3, 6, 9, 12]);
+        let values: Vec<i32> = bst.iter().copied().collect();
+        assert_eq!(values.len(), 55);
+        assert_eq!(bst.summary(), Some(&Keys(values)));
+
+        assert_eq!(bst.extract_if(|value| value % 7 == 0).count(), 8);
+        let values: Vec<i32> = bst.iter().copied().collect();
+        assert_eq!(bst.summary(), Some(&Keys(values)));
+        assert_eq!(bst.extract_if(|_| false).count(), 0);
+        assert_eq!(bst.len(), 47);
+    }
+
+    #[test]
+    fn split_off() {
         let mut bst: BST<i32> = BST::new();
-        bst.insert(5);
-        bst.insert(3);
-        bst.insert(7);
-        bst.insert(6);
-        bst.insert(2);
-        bst.insert(4);
-        bst.insert(9);
-        bst.insert(8);
-        bst.insert(10);
-        bst.insert(1);
+        for value in 0..100 {
+            bst.insert(value);
+        }
+        for at in [-5, 0, 1, 37, 64, 99, 150] {
+            let mut below = BST::new();
+            for value in 0..100 {
+                below.insert(value);
+            }
+            let above = below.split_off(&at);
+            assert_balanced(&below.root);
+            assert_balanced(&above.root);
+            assert_eq!(
+                below.iter().copied().collect::<Vec<i32>>(),
+                (0..at.clamp(0, 100)).collect::<Vec<i32>>()
+            );
+            assert_eq!(
+                above.iter().copied().collect::<Vec<i32>>(),
+                (at.clamp(0, 100)..100).collect::<Vec<i32>>()
+            );
+        }
+        let (below, found, above) = bst.split(&40);
+        assert_eq!(found, Some(40));
+        assert_eq!(below.len(), 40);
+        assert_eq!(above.len(), 59);
+        assert_balanced(&below.root);
+        assert_balanced(&above.root);
+    }
 
-        assert_eq!(
-            bst,
-            BST {
-                root: Node::new_node(
-                    5,
-                    Node::new_node(
-                        3,
-                        Node::new_node(2, Node::new_node(1, None, None), None),
-                        Node::new_node(4, None, None),
-                    ),
-                    Node::new_node(
-                        7,
-                        Node::new_node(6, None, None),
-                        Node::new_node(
-                            9,
-                            Node::new_node(8, None, None),
-                            Node::new_node(10, None, None)
-                        )
-                    )
-                )
+    #[test]
+    fn join() {
+        for (left_len, right_len) in [(0, 0), (0, 10), (10, 0), (1000, 3), (3, 1000), (64, 64)] {
+            let mut left: BST<i32> = BST::new();
+            for value in 0..left_len {
+                left.insert(value);
             }
-        );
+            let mut right: BST<i32> = BST::new();
+            for value in left_len + 1..left_len + 1 + right_len {
+                right.insert(value);
+            }
+            let joined = BST::join(left, left_len, right);
+            assert_balanced(&joined.root);
+            assert_eq!(
+                joined.iter().copied().collect::<Vec<i32>>(),
+                (0..=left_len + right_len).collect::<Vec<i32>>()
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "joined trees must be ordered around the pivot")]
+    fn join_unordered() {
+        let mut left: BST<i32> = BST::new();
+        left.insert(5);
+        BST::join(left, 3, BST::new());
+    }
+
+    #[test]
+    fn append() {
+        let mut low: BST<i32> = BST::new();
+        let mut high: BST<i32> = BST::new();
+        for value in 0..50 {
+            low.insert(value);
+            high.insert(value + 500);
+        }
+        high.append(&mut low);
+        assert!(low.is_empty());
+        assert_balanced(&high.root);
+        assert_eq!(high.len(), 100);
+        assert_eq!(high.first(), Some(&0));
+
+        let mut mixed: BST<i32> = BST::new();
+        for value in (0..600).step_by(7) {
+            mixed.insert(value);
+        }
+        high.append(&mut mixed);
+        assert_balanced(&high.root);
+        let mut expected: Vec<i32> = (0..50).chain(500..550).chain((0..600).step_by(7)).collect();
+        expected.sort();
+        expected.dedup();
+        assert_eq!(high.iter().copied().collect::<Vec<i32>>(), expected);
+    }
+
+    #[test]
+    fn from_sorted_iter() {
+        for len in [0, 1, 2, 3, 7, 8, 100, 1023, 1024] {
+            let bst: BST<i32> = BST::from_sorted_iter(0..len);
+            assert_balanced(&bst.root);
+            assert_eq!(bst.len(), len as usize);
+            let perfect_height = i32::BITS - len.leading_zeros();
+            assert_eq!(Node::height_of(&bst.root), perfect_height);
+            assert!(bst.iter().copied().eq(0..len));
+        }
+        let bst: BST<i32> = BST::from_sorted_iter_unchecked([1, 2, 3]);
+        assert!(bst.contains(&2));
+    }
+
+    #[test]
+    #[should_panic(expected = "values must be strictly increasing")]
+    fn from_sorted_iter_unsorted() {
+        BST::<i32>::from_sorted_iter([1, 3, 3]);
+    }
 
-        bst.delete(5);
+    #[test]
+    fn from_iter_and_extend() {
+        let bst: BST<i32> = [5, 3, 9, 3, 1, 5, 7].into_iter().collect();
+        assert_balanced(&bst.root);
+        assert_eq!(bst.iter().copied().collect::<Vec<i32>>(), [1, 3, 5, 7, 9]);
 
+        let mut bst = BST::from([10, 20, 30]);
+        bst.extend([25, 5, 20, 35]);
+        bst.extend(&[1, 2]);
+        assert_balanced(&bst.root);
         assert_eq!(
-            bst,
-            BST {
-                root: Node::new_node(
-                    6,
-                    Node::new_node(
-                        3,
-                        Node::new_node(2, Node::new_node(1, None, None), None),
-                        Node::new_node(4, None, None),
-                    ),
-                    Node::new_node(
-                        9,
-                        Node::new_node(7, None, Node::new_node(8, None, None)),
-                        Node::new_node(10, None, None)
-                    )
-                )
+            bst.iter().copied().collect::<Vec<i32>>(),
+            [1, 2, 5, 10, 20, 25, 30, 35]
+        );
+
+        let mut bst: BST<i32> = (0..1000).collect();
+        bst.extend(1000..2000);
+        assert_balanced(&bst.root);
+        assert_eq!(bst.len(), 2000);
+    }
+
+    #[test]
+    fn retain() {
+        let mut bst: BST<i32> = (0..500).collect();
+        let mut visited = Vec::new();
+        bst.retain(|value| {
+            visited.push(*value);
+            value % 3 == 0
+        });
+        assert_eq!(visited, (0..500).collect::<Vec<i32>>());
+        assert_balanced(&bst.root);
+        assert!(bst.iter().copied().eq((0..500).step_by(3)));
+
+        bst.retain(|value| *value > 450);
+        assert_balanced(&bst.root);
+        assert!(bst.iter().copied().eq((453..500).step_by(3)));
+        bst.retain(|_| false);
+        assert!(bst.is_empty());
+    }
+
+    #[test]
+    fn extract_if() {
+        let mut bst: BST<i32> = (0..100).collect();
+        let extracted: Vec<i32> = bst.extract_if(|value| value % 4 == 1).collect();
+        assert!(extracted.into_iter().eq((1..100).step_by(4)));
+        assert_balanced(&bst.root);
+        assert_eq!(bst.len(), 75);
+
+        let mut iter = bst.extract_if(|value| *value >= 50);
+        assert_eq!(iter.next(), Some(50));
+        assert_eq!(iter.next(), Some(51));
+        drop(iter);
+        assert_balanced(&bst.root);
+        assert!(!bst.contains(&51));
+        assert!(bst.contains(&52));
+        assert_eq!(bst.extract_if(|_| true).count(), 73);
+        assert!(bst.is_empty());
+    }
+
+    #[test]
+    fn cursor_edits_keep_balance() {
+        let mut bst: BST<i32> = (0..100).map(|value| value * 2).collect();
+        let mut cursor = bst.upper_bound_cursor_mut(Bound::Included(&51));
+        assert_eq!(cursor.peek(), Some(&50));
+        for _ in 0..20 {
+            let value = *cursor.peek().unwrap();
+            cursor.insert_after(value + 1);
+            cursor.move_prev();
+        }
+        assert_eq!(cursor.peek(), Some(&10));
+        while cursor.peek().is_some_and(|value| *value < 30) {
+            cursor.remove_current();
+        }
+        assert_eq!(cursor.peek(), Some(&30));
+        assert_eq!(cursor.index(), Some(5));
+        assert_balanced(&bst.root);
+        assert_eq!(bst.len(), 101);
+
+        let cursor = bst.lower_bound_cursor(Bound::Included(&30));
+        let mut back = cursor.clone();
+        back.move_prev();
+        assert_eq!((back.peek(), cursor.peek_next()), (Some(&8), Some(&31)));
+    }
+
+    #[test]
+    fn mixed_updates_match_btreeset() {
+        let mut bst = BST::new();
+        let mut expected = std::collections::BTreeSet::new();
+        for i in 0..600u32 {
+            let value = (i * 7919) % 211;
+            match i % 6 {
+                0 | 1 => assert_eq!(bst.insert(value), expected.insert(value)),
+                2 => assert_eq!(bst.replace(value), expected.replace(value)),
+                3 => assert_eq!(bst.remove(&value), expected.remove(&value)),
+                4 => assert_eq!(bst.pop_first(), expected.pop_first()),
+                _ => {
+                    if let Entry::Occupied(entry) = bst.entry(value) {
+                        assert_eq!(entry.remove(), value);
+                        expected.remove(&value);
+                    }
+                }
             }
+            assert_balanced(&bst.root);
+        }
+        assert!(bst.iter().eq(expected.iter()));
+        while let Some(value) = expected.pop_last() {
+            assert_eq!(bst.pop_last(), Some(value));
+        }
+        assert!(bst.is_empty());
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn compact_node_layout() {
+        // Key, two links and the packed shape; `BST<u64>` stores `Node<u64, ()>`.
+        assert_eq!(mem::size_of::<Node<u64, ()>>(), 32);
+        assert_eq!(mem::size_of::<Node<u32, ()>>(), 32);
+        assert_eq!(mem::size_of::<Node<u64, u64>>(), 40);
+    }
+
+    #[test]
+    fn shape_round_trip() {
+        let size = usize::try_from((1u64 << Shape::SIZE_BITS) - 1).unwrap_or(usize::MAX);
+        let shape = Shape::new(MAX_DEPTH as u32, size);
+        assert_eq!(shape.height(), MAX_DEPTH as u32);
+        assert_eq!(shape.size(), size);
+        assert_eq!(
+            format!("{:?}", Shape::new(2, 5)),
+            "Shape { height: 2, size: 5 }"
         );
     }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    #[should_panic(expected = "tree size exceeds the shape's size bits")]
+    fn shape_size_overflow() {
+        Shape::new(0, 1 << Shape::SIZE_BITS);
+    }
 }
@@ -23,28 +23,28 @@ fn main() {
     println!("BST: {:?}", bst);
 
     println!("***********************************************");
-    println!("BST contains 15: {}", bst.contains(15));
-    println!("BST contains 1968: {}", bst.contains(1968));
+    println!("BST contains 15: {}", bst.contains(&15));
+    println!("BST contains 1968: {}", bst.contains(&1968));
     println!("***********************************************");
 
     println!("BST: {:?}", bst);
     println!("***********************************************");
-    bst.delete(15);
+    bst.remove(&15);
     println!("BST: {:?}", bst);
     println!("***********************************************");
-    bst.delete(17);
+    bst.remove(&17);
     println!("BST: {:?}", bst);
     println!("***********************************************");
-    bst.delete(1968);
+    bst.remove(&1968);
     println!("BST: {:?}", bst);
     println!("***********************************************");
-    bst.delete(5);
+    bst.remove(&5);
     println!("BST: {:?}", bst);
     println!("***********************************************");
-    bst.delete(11);
+    bst.remove(&11);
     println!("BST: {:?}", bst);
     println!("***********************************************");
-    bst.delete(19);
+    bst.remove(&19);
     println!("BST: {:?}", bst);
     println!("***********************************************");
 
@@ -58,16 +58,16 @@ fn main() {
     println!("***********************************************");
     println!("BST: {:?}", bst);
 
-    bst.delete(18);
+    bst.remove(&18);
     println!("BST: {:?}", bst);
     println!("***********************************************");
-    bst.delete(12);
+    bst.remove(&12);
     println!("BST: {:?}", bst);
     println!("***********************************************");
-    bst.delete(10);
+    bst.remove(&10);
     println!("BST: {:?}", bst);
     println!("***********************************************");
-    bst.delete(13);
+    bst.remove(&13);
     println!("BST: {:?}", bst);
     println!("***********************************************");
 
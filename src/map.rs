@@ -0,0 +1,1680 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::iter::FusedIterator;
+use std::mem;
+use std::ops::{Bound, RangeBounds};
+
+use crate::{
+    check_range, Augment, Comparator, Link, Node, NodeConsumingIter, NodeExtractIf, NodeRefIter,
+    OrdComparator, Subtree, Zipper,
+};
+
+impl<'a, K, V, C, A> IntoIterator for &'a BSTMap<K, V, C, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, C> IntoIterator for &'a mut BSTMap<K, V, C> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, C, A> IntoIterator for BSTMap<K, V, C, A> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: NodeConsumingIter::new(self.root),
+        }
+    }
+}
+
+/// In-order iterator over the entries of a `BSTMap`.
+pub struct Iter<'a, K, V, A = ()> {
+    inner: NodeRefIter<'a, K, V, A>,
+}
+
+impl<'a, K, V, A> Iterator for Iter<'a, K, V, A> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, A> DoubleEndedIterator for Iter<'a, K, V, A> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, K, V, A> ExactSizeIterator for Iter<'a, K, V, A> {}
+
+impl<'a, K, V, A> FusedIterator for Iter<'a, K, V, A> {}
+
+enum StackMutMember<'a, K, V, A> {
+    Node(&'a mut Node<K, V, A>),
+    Visited(&'a K, &'a mut V),
+}
+
+/// In-order iterator over the entries of a `BSTMap` with mutable values.
+pub struct IterMut<'a, K, V, A = ()> {
+    stack: VecDeque<StackMutMember<'a, K, V, A>>,
+    remaining: usize,
+}
+
+impl<'a, K, V, A> Iterator for IterMut<'a, K, V, A> {
+    type Item = (&'a K, &'a mut V);
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        while let Some(stack_member) = self.stack.pop_front() {
+            match stack_member {
+                StackMutMember::Visited(key, value) => {
+                    self.remaining -= 1;
+                    return Some((key, value));
+                }
+                StackMutMember::Node(node) => {
+                    let Node {
+                        key,
+                        value,
+                        left,
+                        right,
+                        ..
+                    } = node;
+                    if let Some(right) = right {
+                        self.stack.push_front(StackMutMember::Node(right));
+                    }
+                    self.stack.push_front(StackMutMember::Visited(key, value));
+                    if let Some(left) = left {
+                        self.stack.push_front(StackMutMember::Node(left));
+                    }
+                }
+            }
+        }
+        None
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V, A> DoubleEndedIterator for IterMut<'a, K, V, A> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a mut V)> {
+        while let Some(stack_member) = self.stack.pop_back() {
+            match stack_member {
+                StackMutMember::Visited(key, value) => {
+                    self.remaining -= 1;
+                    return Some((key, value));
+                }
+                StackMutMember::Node(node) => {
+                    let Node {
+                        key,
+                        value,
+                        left,
+                        right,
+                        ..
+                    } = node;
+                    if let Some(left) = left {
+                        self.stack.push_back(StackMutMember::Node(left));
+                    }
+                    self.stack.push_back(StackMutMember::Visited(key, value));
+                    if let Some(right) = right {
+                        self.stack.push_back(StackMutMember::Node(right));
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<'a, K, V, A> ExactSizeIterator for IterMut<'a, K, V, A> {}
+
+impl<'a, K, V, A> FusedIterator for IterMut<'a, K, V, A> {}
+
+/// In-order iterator over the entries of a `BSTMap` within a range of keys.
+pub struct Range<'a, K, V, A = ()> {
+    inner: NodeRefIter<'a, K, V, A>,
+}
+
+impl<'a, K, V, A> Iterator for Range<'a, K, V, A> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, A> DoubleEndedIterator for Range<'a, K, V, A> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, K, V, A> ExactSizeIterator for Range<'a, K, V, A> {}
+
+impl<'a, K, V, A> FusedIterator for Range<'a, K, V, A> {}
+
+/// In-order iterator over the keys of a `BSTMap`.
+pub struct Keys<'a, K, V, A = ()> {
+    inner: Iter<'a, K, V, A>,
+}
+
+impl<'a, K, V, A> Iterator for Keys<'a, K, V, A> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next().map(|(key, _)| key)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, A> DoubleEndedIterator for Keys<'a, K, V, A> {
+    fn next_back(&mut self) -> Option<&'a K> {
+        self.inner.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<'a, K, V, A> ExactSizeIterator for Keys<'a, K, V, A> {}
+
+impl<'a, K, V, A> FusedIterator for Keys<'a, K, V, A> {}
+
+/// Iterator over the values of a `BSTMap`, in the order of their keys.
+pub struct Values<'a, K, V, A = ()> {
+    inner: Iter<'a, K, V, A>,
+}
+
+impl<'a, K, V, A> Iterator for Values<'a, K, V, A> {
+    type Item = &'a V;
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, A> DoubleEndedIterator for Values<'a, K, V, A> {
+    fn next_back(&mut self) -> Option<&'a V> {
+        self.inner.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<'a, K, V, A> ExactSizeIterator for Values<'a, K, V, A> {}
+
+impl<'a, K, V, A> FusedIterator for Values<'a, K, V, A> {}
+
+/// Mutable iterator over the values of a `BSTMap`, in the order of their keys.
+pub struct ValuesMut<'a, K, V, A = ()> {
+    inner: IterMut<'a, K, V, A>,
+}
+
+impl<'a, K, V, A> Iterator for ValuesMut<'a, K, V, A> {
+    type Item = &'a mut V;
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.inner.next().map(|(_, value)| value)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, A> DoubleEndedIterator for ValuesMut<'a, K, V, A> {
+    fn next_back(&mut self) -> Option<&'a mut V> {
+        self.inner.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<'a, K, V, A> ExactSizeIterator for ValuesMut<'a, K, V, A> {}
+
+impl<'a, K, V, A> FusedIterator for ValuesMut<'a, K, V, A> {}
+
+/// Consuming in-order iterator over the entries of a `BSTMap`.
+pub struct IntoIter<K, V, A = ()> {
+    inner: NodeConsumingIter<K, V, A>,
+}
+
+impl<K, V, A> Iterator for IntoIter<K, V, A> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<(K, V)> {
+        self.inner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V, A> DoubleEndedIterator for IntoIter<K, V, A> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        self.inner.next_back()
+    }
+}
+
+impl<K, V, A> ExactSizeIterator for IntoIter<K, V, A> {}
+
+impl<K, V, A> FusedIterator for IntoIter<K, V, A> {}
+
+/// Lazy iterator removing the entries of a `BSTMap` that match a predicate,
+/// obtained from `BSTMap::extract_if`.
+///
+/// Entries not yet reached when the iterator is dropped stay in the map.
+pub struct ExtractIf<'a, K, V, F, A: Augment<K, V> = ()> {
+    inner: NodeExtractIf<'a, K, V, A>,
+    pred: F,
+}
+
+impl<'a, K, V, F: FnMut(&K, &mut V) -> bool, A: Augment<K, V>> Iterator
+    for ExtractIf<'a, K, V, F, A>
+{
+    type Item = (K, V);
+    fn next(&mut self) -> Option<(K, V)> {
+        self.inner.next(&mut self.pred)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, F: FnMut(&K, &mut V) -> bool, A: Augment<K, V>> FusedIterator
+    for ExtractIf<'a, K, V, F, A>
+{
+}
+
+/// A view into a single entry of a `BSTMap`, obtained from `BSTMap::entry`.
+pub enum Entry<'a, K, V, A: Augment<K, V> = ()> {
+    Occupied(OccupiedEntry<'a, K, V, A>),
+    Vacant(VacantEntry<'a, K, V, A>),
+}
+
+/// An entry whose key is present in the map.
+pub struct OccupiedEntry<'a, K, V, A: Augment<K, V> = ()> {
+    zipper: Zipper<'a, K, V, A>,
+}
+
+/// An entry whose key is missing from the map.
+pub struct VacantEntry<'a, K, V, A: Augment<K, V> = ()> {
+    key: K,
+    zipper: Zipper<'a, K, V, A>,
+}
+
+/// Descends from the root of `zipper` toward `key`, stopping at the node
+/// holding it or at the empty link where it belongs. Returns whether it was
+/// found.
+fn descend_to<Q: ?Sized, K: Borrow<Q>, V, A: Augment<K, V>, C: Comparator<Q>>(
+    zipper: &mut Zipper<'_, K, V, A>,
+    key: &Q,
+    cmp: &C,
+) -> bool {
+    while let Some(node) = &zipper.focus {
+        match cmp.compare(key, node.key.borrow()) {
+            Ordering::Equal => return true,
+            step => zipper.descend(step),
+        }
+    }
+    false
+}
+
+impl<'a, K, V, A: Augment<K, V>> Entry<'a, K, V, A> {
+    /// Looks `key` up in the tree rooted at `root` with a single descent.
+    ///
+    /// The entry keeps the tree unzipped along that descent, so reading,
+    /// inserting or removing through it compares no keys again and
+    /// rebalances on the way back up.
+    pub(crate) fn search<C: Comparator<K>>(
+        root: &'a mut Link<K, V, A>,
+        key: K,
+        cmp: &C,
+    ) -> Entry<'a, K, V, A> {
+        let mut zipper = Zipper::new(root);
+        if descend_to(&mut zipper, &key, cmp) {
+            Entry::Occupied(OccupiedEntry { zipper })
+        } else {
+            Entry::Vacant(VacantEntry { key, zipper })
+        }
+    }
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+    /// Applies `f` to the value if the entry is occupied. The summaries
+    /// above it are refreshed once the entry is dropped.
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            entry.modify(f);
+        }
+        self
+    }
+}
+
+/// Mutable access to values, for maps without a summary (see `Augment`).
+impl<'a, K, V> Entry<'a, K, V> {
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        self.or_insert_with_key(|_| default())
+    }
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> OccupiedEntry<'a, K, V, A> {
+    /// Looks up a borrowed `key`, which can only yield an occupied entry.
+    pub(crate) fn search<Q: ?Sized, C: Comparator<Q>>(
+        root: &'a mut Link<K, V, A>,
+        key: &Q,
+        cmp: &C,
+    ) -> Option<OccupiedEntry<'a, K, V, A>>
+    where
+        K: Borrow<Q>,
+    {
+        let mut zipper = Zipper::new(root);
+        descend_to(&mut zipper, key, cmp).then_some(OccupiedEntry { zipper })
+    }
+    pub fn key(&self) -> &K {
+        &self.zipper.node().key
+    }
+    pub fn get(&self) -> &V {
+        &self.zipper.node().value
+    }
+    /// Applies `f` to the value, which marks the summaries above it for
+    /// refreshing.
+    fn modify<R, F: FnOnce(&mut V) -> R>(&mut self, f: F) -> R {
+        let node = self.zipper.node_mut();
+        let result = f(&mut node.value);
+        node.update_size();
+        result
+    }
+    /// Replaces the value of the entry, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        self.modify(|old| mem::replace(old, value))
+    }
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+    /// Removes the entry from the map, returning the stored key and value.
+    pub fn remove_entry(mut self) -> (K, V) {
+        self.zipper.remove_focus().0
+    }
+    /// Converts the entry into a reference to its key, for the set entries.
+    pub(crate) fn into_key_ref(self) -> &'a K {
+        &self.zipper.into_node().key
+    }
+}
+
+/// Mutable access to values, for maps without a summary (see `Augment`).
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.zipper.node_mut().value
+    }
+    /// Converts the entry into a mutable reference to its value.
+    ///
+    /// The tree is put back together first, so the node is found again by
+    /// the turns of the descent, in O(log n) without comparisons.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.zipper.into_node().value
+    }
+}
+
+impl<'a, K, V, A: Augment<K, V>> VacantEntry<'a, K, V, A> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+    pub fn into_key(self) -> K {
+        self.key
+    }
+    /// Inserts `value` under the entry's key and rebalances, returning the
+    /// new node.
+    ///
+    /// Rebalancing may move the node, so it is found again by its position,
+    /// in O(log n) without comparisons.
+    fn insert_node(self, value: V) -> &'a mut Node<K, V, A> {
+        let VacantEntry { key, mut zipper } = self;
+        let index = zipper.insert_focus(key, value);
+        zipper
+            .into_root()
+            .as_deref_mut()
+            .and_then(|root| root.select_mut(index))
+            .expect("tree holds the inserted node")
+    }
+    /// Inserts `value` under the entry's key and returns an entry for it.
+    ///
+    /// Rebalancing may move the new node, so it is found again by its
+    /// position, in O(log n) without comparisons.
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, A> {
+        let VacantEntry { key, mut zipper } = self;
+        let index = zipper.insert_focus(key, value);
+        zipper.seek(index);
+        OccupiedEntry { zipper }
+    }
+    /// Inserts `value` under the entry's key without handing out the new
+    /// entry, which saves finding it again after rebalancing.
+    pub(crate) fn insert_only(self, value: V) {
+        let VacantEntry { key, mut zipper } = self;
+        zipper.insert_focus(key, value);
+    }
+    /// Inserts `value` under the entry's key and returns a reference to the
+    /// key, for the set entries.
+    pub(crate) fn insert_key_ref(self, value: V) -> &'a K {
+        &self.insert_node(value).key
+    }
+}
+
+/// Mutable access to values, for maps without a summary (see `Augment`).
+impl<'a, K, V> VacantEntry<'a, K, V> {
+    /// Inserts `value` under the entry's key and returns a reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        &mut self.insert_node(value).value
+    }
+}
+
+/// Returns the position after `index` among `len` positions plus the ghost.
+fn next_index(index: usize, len: usize) -> usize {
+    if index >= len {
+        0
+    } else {
+        index + 1
+    }
+}
+
+/// Returns the position before `index` among `len` positions plus the ghost.
+fn prev_index(index: usize, len: usize) -> usize {
+    if index == 0 {
+        len
+    } else {
+        index - 1
+    }
+}
+
+/// A cursor over the entries of a `BSTMap`, obtained from
+/// `BSTMap::lower_bound_cursor` or `BSTMap::upper_bound_cursor`.
+///
+/// The cursor points at an entry or at a "ghost" position that sits both
+/// after the last entry and before the first one, so moving past either end
+/// reaches the ghost and moving on from it wraps around. The cursor tracks
+/// its position by index, and every move or peek takes O(log n).
+pub struct Cursor<'a, K, V, A = ()> {
+    root: &'a Link<K, V, A>,
+    /// Position of the current entry, or the number of entries at the ghost.
+    index: usize,
+}
+
+impl<'a, K, V, A> Clone for Cursor<'a, K, V, A> {
+    fn clone(&self) -> Self {
+        Cursor {
+            root: self.root,
+            index: self.index,
+        }
+    }
+}
+
+impl<'a, K, V, A> Cursor<'a, K, V, A> {
+    pub(crate) fn new(root: &'a Link<K, V, A>, index: usize) -> Cursor<'a, K, V, A> {
+        Cursor { root, index }
+    }
+    fn entry(&self, index: usize) -> Option<(&'a K, &'a V)> {
+        self.root
+            .as_deref()
+            .and_then(|root| root.select(index))
+            .map(|node| (&node.key, &node.value))
+    }
+    /// Returns the position of the current entry in key order, or `None` at
+    /// the ghost.
+    pub fn index(&self) -> Option<usize> {
+        (self.index < Node::size_of(self.root)).then_some(self.index)
+    }
+    pub fn move_next(&mut self) {
+        self.index = next_index(self.index, Node::size_of(self.root));
+    }
+    pub fn move_prev(&mut self) {
+        self.index = prev_index(self.index, Node::size_of(self.root));
+    }
+    /// Returns the current entry, or `None` at the ghost.
+    pub fn peek(&self) -> Option<(&'a K, &'a V)> {
+        self.entry(self.index)
+    }
+    /// Returns the entry `move_next` would move to.
+    pub fn peek_next(&self) -> Option<(&'a K, &'a V)> {
+        self.entry(next_index(self.index, Node::size_of(self.root)))
+    }
+    /// Returns the entry `move_prev` would move to.
+    pub fn peek_prev(&self) -> Option<(&'a K, &'a V)> {
+        self.entry(prev_index(self.index, Node::size_of(self.root)))
+    }
+}
+
+/// A cursor that can also edit a `BSTMap`, obtained from
+/// `BSTMap::lower_bound_cursor_mut` or `BSTMap::upper_bound_cursor_mut`.
+///
+/// It moves like `Cursor`. Every edit rebalances the tree in O(log n) before
+/// returning, and keeps the cursor on the same entry, except for
+/// `remove_current`, which moves it to the next one.
+pub struct CursorMut<'a, K, V, C, A = ()> {
+    root: &'a mut Link<K, V, A>,
+    comparator: &'a C,
+    /// Position of the current entry, or the number of entries at the ghost.
+    index: usize,
+}
+
+impl<'a, K, V, C: Comparator<K>, A: Augment<K, V>> CursorMut<'a, K, V, C, A> {
+    pub(crate) fn new(
+        root: &'a mut Link<K, V, A>,
+        comparator: &'a C,
+        index: usize,
+    ) -> CursorMut<'a, K, V, C, A> {
+        CursorMut {
+            root,
+            comparator,
+            index,
+        }
+    }
+    /// Returns a read-only cursor at the same position.
+    pub fn as_cursor(&self) -> Cursor<'_, K, V, A> {
+        Cursor::new(self.root, self.index)
+    }
+    /// Returns the position of the current entry in key order, or `None` at
+    /// the ghost.
+    pub fn index(&self) -> Option<usize> {
+        self.as_cursor().index()
+    }
+    pub fn move_next(&mut self) {
+        self.index = next_index(self.index, Node::size_of(self.root));
+    }
+    pub fn move_prev(&mut self) {
+        self.index = prev_index(self.index, Node::size_of(self.root));
+    }
+    /// Returns the entry `move_next` would move to.
+    pub fn peek_next(&self) -> Option<(&K, &V)> {
+        self.as_cursor().peek_next()
+    }
+    /// Returns the entry `move_prev` would move to.
+    pub fn peek_prev(&self) -> Option<(&K, &V)> {
+        self.as_cursor().peek_prev()
+    }
+    /// Removes and returns the current entry, moving the cursor to the next
+    /// one. Does nothing at the ghost.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        if self.index >= Node::size_of(self.root) {
+            return None;
+        }
+        let mut zipper = Zipper::new(self.root);
+        zipper.seek(self.index);
+        Some(zipper.remove_focus().0)
+    }
+    /// Inserts an entry between the previous entry and the current one.
+    ///
+    /// At the ghost, the entry becomes the last one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is not greater than the previous key and smaller than
+    /// the current one.
+    pub fn insert_before(&mut self, key: K, value: V) {
+        let cursor = self.as_cursor();
+        self.check_order(cursor.peek_prev(), &key, cursor.peek());
+        self.insert(key, value);
+        self.index += 1;
+    }
+    /// Inserts an entry between the current entry and the next one.
+    ///
+    /// At the ghost, the entry becomes the first one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is not greater than the current key and smaller than
+    /// the next one.
+    pub fn insert_after(&mut self, key: K, value: V) {
+        let cursor = self.as_cursor();
+        let at_ghost = cursor.index().is_none();
+        self.check_order(cursor.peek(), &key, cursor.peek_next());
+        self.insert(key, value);
+        if at_ghost {
+            self.index += 1;
+        }
+    }
+    fn check_order(&self, prev: Option<(&K, &V)>, key: &K, next: Option<(&K, &V)>) {
+        let ordered = prev.is_none_or(|(prev, _)| self.comparator.compare(prev, key).is_lt())
+            && next.is_none_or(|(next, _)| self.comparator.compare(key, next).is_lt());
+        assert!(ordered, "inserted key must be ordered around the cursor");
+    }
+    fn insert(&mut self, key: K, value: V) {
+        Node::insert_into(self.root, key, value, self.comparator);
+    }
+}
+
+/// Mutable access to values, for maps without a summary (see `Augment`).
+impl<'a, K, V, C: Comparator<K>> CursorMut<'a, K, V, C> {
+    /// Returns the current entry, or `None` at the ghost.
+    pub fn peek(&mut self) -> Option<(&K, &mut V)> {
+        let node = self.root.as_deref_mut()?.select_mut(self.index)?;
+        Some((&node.key, &mut node.value))
+    }
+}
+
+/// An ordered map backed by the same AVL tree as `BST`.
+#[derive(Debug)]
+pub struct BSTMap<K, V, C = OrdComparator, A = ()> {
+    root: Option<Box<Node<K, V, A>>>,
+    comparator: C,
+}
+
+/// Maps are equal when they hold equal entries, whatever their shape.
+impl<K: PartialEq, V: PartialEq, C, A> PartialEq for BSTMap<K, V, C, A> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, V: Eq, C, A> Eq for BSTMap<K, V, C, A> {}
+
+impl<K, V, C: Comparator<K> + Default, A: Augment<K, V>> Default for BSTMap<K, V, C, A> {
+    fn default() -> Self {
+        Self::with_augment(C::default())
+    }
+}
+
+impl<K: Ord, V> BSTMap<K, V> {
+    pub fn new() -> BSTMap<K, V> {
+        BSTMap::with_comparator(OrdComparator)
+    }
+}
+
+/// Builds the map in O(n) if the keys arrive sorted, and sorts them first
+/// otherwise. The last value given for a key wins.
+impl<K, V, C: Comparator<K> + Default, A: Augment<K, V>> FromIterator<(K, V)>
+    for BSTMap<K, V, C, A>
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let comparator = C::default();
+        BSTMap {
+            root: Node::from_unsorted(iter.into_iter().collect(), &comparator, true),
+            comparator,
+        }
+    }
+}
+
+/// Builds a tree of the new entries and merges it in with a join-based
+/// union. New values replace old ones.
+impl<K, V, C: Comparator<K>, A: Augment<K, V>> Extend<(K, V)> for BSTMap<K, V, C, A> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let other = Node::from_unsorted(iter.into_iter().collect(), &self.comparator, true);
+        self.root = Node::union(other, self.root.take(), &self.comparator);
+    }
+}
+
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for BSTMap<K, V> {
+    fn from(entries: [(K, V); N]) -> BSTMap<K, V> {
+        BSTMap::from_iter(entries)
+    }
+}
+
+impl<K, V, C: Comparator<K>> BSTMap<K, V, C> {
+    /// Creates an empty map whose keys are ordered by `comparator`.
+    pub fn with_comparator(comparator: C) -> BSTMap<K, V, C> {
+        BSTMap::with_augment(comparator)
+    }
+    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.root
+            .as_mut()
+            .and_then(|root| root.find_mut(key, &self.comparator))
+            .map(|node| &mut node.value)
+    }
+}
+
+impl<K, V, C: Comparator<K>, A: Augment<K, V>> BSTMap<K, V, C, A> {
+    /// Creates an empty map ordered by `comparator` whose nodes keep an `A`
+    /// summary of the entries in their subtree.
+    pub fn with_augment(comparator: C) -> BSTMap<K, V, C, A> {
+        BSTMap {
+            root: None,
+            comparator,
+        }
+    }
+    pub fn comparator(&self) -> &C {
+        &self.comparator
+    }
+    /// Inserts `value` under `key`, returning the value it replaced.
+    ///
+    /// The key already stored in the map is kept if there was one.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        Node::insert_into(&mut self.root, key, value, &self.comparator)
+    }
+    /// Returns the value stored under `key`.
+    ///
+    /// `key` may be any borrowed form of `K`, e.g. `&str` for `String` keys.
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.get_key_value(key).map(|(_, value)| value)
+    }
+    /// Returns the stored key equal to `key` together with its value.
+    pub fn get_key_value<Q: ?Sized>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.root
+            .as_ref()
+            .and_then(|root| root.find(key, &self.comparator))
+            .map(|node| (&node.key, &node.value))
+    }
+    /// Returns the entry with the smallest key.
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.root.as_ref().map(|root| {
+            let node = root.leftmost();
+            (&node.key, &node.value)
+        })
+    }
+    /// Returns the entry with the greatest key.
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.root.as_ref().map(|root| {
+            let node = root.rightmost();
+            (&node.key, &node.value)
+        })
+    }
+    /// Returns the number of keys smaller than `key`.
+    pub fn rank<Q: ?Sized>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.root.as_ref().map_or(0, |root| {
+            root.count_below(&(Bound::Included(key), Bound::Unbounded), &self.comparator)
+        })
+    }
+    /// Returns the number of keys within `range` in O(log n).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end, or if both
+    /// are equal and excluded.
+    pub fn count_in_range<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> usize
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        check_range(&range, &self.comparator);
+        self.root.as_ref().map_or(0, |root| {
+            root.size()
+                - root.count_below(&range, &self.comparator)
+                - root.count_above(&range, &self.comparator)
+        })
+    }
+    /// Combines the summaries of the entries whose keys are within `range`,
+    /// in O(log n).
+    ///
+    /// Returns `None` if the range is empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end, or if both
+    /// are equal and excluded.
+    pub fn fold_range<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> Option<A>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        check_range(&range, &self.comparator);
+        self.root
+            .as_ref()
+            .and_then(|root| root.fold_range(&range, &self.comparator))
+    }
+    /// Returns the entry with the smallest key above `bound`.
+    pub fn lower_bound<Q: ?Sized>(&self, bound: Bound<&Q>) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.root
+            .as_ref()
+            .and_then(|root| root.first_in(&(bound, Bound::Unbounded), &self.comparator))
+            .map(|node| (&node.key, &node.value))
+    }
+    /// Returns the entry with the greatest key below `bound`.
+    pub fn upper_bound<Q: ?Sized>(&self, bound: Bound<&Q>) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.root
+            .as_ref()
+            .and_then(|root| root.last_in(&(Bound::Unbounded, bound), &self.comparator))
+            .map(|node| (&node.key, &node.value))
+    }
+    /// Returns a cursor at the entry `lower_bound` would return, or at the
+    /// ghost if there is none.
+    pub fn lower_bound_cursor<Q: ?Sized>(&self, bound: Bound<&Q>) -> Cursor<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        Cursor::new(&self.root, self.lower_bound_index(bound))
+    }
+    /// Returns a cursor at the entry `upper_bound` would return, or at the
+    /// ghost if there is none.
+    pub fn upper_bound_cursor<Q: ?Sized>(&self, bound: Bound<&Q>) -> Cursor<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        Cursor::new(&self.root, self.upper_bound_index(bound))
+    }
+    /// Like `lower_bound_cursor`, but the cursor can edit the map.
+    pub fn lower_bound_cursor_mut<Q: ?Sized>(
+        &mut self,
+        bound: Bound<&Q>,
+    ) -> CursorMut<'_, K, V, C, A>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        let index = self.lower_bound_index(bound);
+        CursorMut::new(&mut self.root, &self.comparator, index)
+    }
+    /// Like `upper_bound_cursor`, but the cursor can edit the map.
+    pub fn upper_bound_cursor_mut<Q: ?Sized>(
+        &mut self,
+        bound: Bound<&Q>,
+    ) -> CursorMut<'_, K, V, C, A>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        let index = self.upper_bound_index(bound);
+        CursorMut::new(&mut self.root, &self.comparator, index)
+    }
+    fn lower_bound_index<Q: ?Sized>(&self, bound: Bound<&Q>) -> usize
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.root.as_ref().map_or(0, |root| {
+            root.count_below(&(bound, Bound::Unbounded), &self.comparator)
+        })
+    }
+    fn upper_bound_index<Q: ?Sized>(&self, bound: Bound<&Q>) -> usize
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        let len = self.len();
+        let not_above = self.root.as_ref().map_or(0, |root| {
+            len - root.count_above(&(Bound::Unbounded, bound), &self.comparator)
+        });
+        not_above.checked_sub(1).unwrap_or(len)
+    }
+    /// Removes and returns the entry with the smallest key.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        Node::delete_first(&mut self.root)
+    }
+    /// Removes and returns the entry with the greatest key.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        Node::delete_last(&mut self.root)
+    }
+    /// Moves all entries with keys `>= key` into a new map, in O(log n).
+    pub fn split_off<Q: ?Sized>(&mut self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q> + Clone,
+    {
+        let (below, found, above) = Node::split(self.root.take(), key, &self.comparator);
+        self.root = below;
+        let above = match found {
+            Some((found, value)) => Node::join(None, found, value, above),
+            None => above,
+        };
+        BSTMap {
+            root: above,
+            comparator: self.comparator.clone(),
+        }
+    }
+    /// Splits the map into the entries with keys below `key`, the entry for
+    /// `key` if there is one, and the entries with keys above `key`, in
+    /// O(log n).
+    pub fn split<Q: ?Sized>(self, key: &Q) -> (Self, Option<(K, V)>, Self)
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q> + Clone,
+    {
+        let (below, found, above) = Node::split(self.root, key, &self.comparator);
+        (
+            BSTMap {
+                root: below,
+                comparator: self.comparator.clone(),
+            },
+            found,
+            BSTMap {
+                root: above,
+                comparator: self.comparator,
+            },
+        )
+    }
+    /// Joins `left`, the entry `key`/`value` and `right` into one map, in
+    /// O(log n).
+    ///
+    /// The map is ordered by the comparator of `left`.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless all keys of `left` are smaller than `key` and all keys
+    /// of `right` are greater.
+    pub fn join(left: Self, key: K, value: V, right: Self) -> Self {
+        let cmp = &left.comparator;
+        assert!(
+            left.last_key_value()
+                .is_none_or(|(last, _)| cmp.compare(last, &key) == Ordering::Less)
+                && right
+                    .first_key_value()
+                    .is_none_or(|(first, _)| cmp.compare(&key, first) == Ordering::Less),
+            "joined maps must be ordered around the pivot"
+        );
+        BSTMap {
+            root: Node::join(left.root, key, value, right.root),
+            comparator: left.comparator,
+        }
+    }
+    /// Moves all entries of `other` into `self`, leaving `other` empty.
+    ///
+    /// Values of `other` replace those of `self` for keys present in both.
+    /// Takes O(log n) when all keys of one map are smaller than all keys of
+    /// the other, and O(m log(n / m + 1)) otherwise, for maps of sizes
+    /// m <= n.
+    pub fn append(&mut self, other: &mut Self) {
+        self.root = Node::append(self.root.take(), other.root.take(), &self.comparator);
+    }
+    /// Keeps only the entries for which `f` returns `true`, visiting them in
+    /// key order.
+    ///
+    /// The tree is rebuilt by joining the kept entries in a single O(n) pass.
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        self.root = Node::retain(self.root.take(), &mut f);
+    }
+    /// Lazily removes and yields, in key order, the entries for which `pred`
+    /// returns `true`.
+    ///
+    /// Each removal rebalances the tree in O(log n), while kept entries take
+    /// O(1) amortized.
+    pub fn extract_if<F: FnMut(&K, &mut V) -> bool>(
+        &mut self,
+        pred: F,
+    ) -> ExtractIf<'_, K, V, F, A> {
+        ExtractIf {
+            inner: NodeExtractIf::new(&mut self.root),
+            pred,
+        }
+    }
+    /// Iterates in key order over the entries whose keys are within `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end, or if both
+    /// are equal and excluded.
+    pub fn range<Q: ?Sized, R: RangeBounds<Q>>(&self, range: R) -> Range<'_, K, V, A>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        Range {
+            inner: NodeRefIter::range(self.root.as_deref(), &range, &self.comparator),
+        }
+    }
+    /// Gets the entry for a borrowed `key` if it is present.
+    pub(crate) fn find_entry<Q: ?Sized>(&mut self, key: &Q) -> Option<OccupiedEntry<'_, K, V, A>>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        OccupiedEntry::search(&mut self.root, key, &self.comparator)
+    }
+    /// Gets the entry for `key` for in-place manipulation.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, A> {
+        Entry::search(&mut self.root, key, &self.comparator)
+    }
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.root
+            .as_ref()
+            .is_some_and(|root| root.contains(key, &self.comparator))
+    }
+    /// Removes `key` from the map, returning the value stored under it.
+    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+    /// Removes `key` from the map, returning the stored key and value.
+    pub fn remove_entry<Q: ?Sized>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        Node::delete_from(&mut self.root, key, &self.comparator)
+    }
+}
+
+impl<K, V, C, A> BSTMap<K, V, C, A> {
+    pub(crate) fn root(&self) -> Option<&Node<K, V, A>> {
+        self.root.as_deref()
+    }
+    pub fn iter(&self) -> Iter<'_, K, V, A> {
+        Iter {
+            inner: NodeRefIter::new(self.root.as_deref()),
+        }
+    }
+    /// Returns the summary of all entries in the map.
+    pub fn summary(&self) -> Option<&A> {
+        self.root.as_ref().map(|root| &root.summary)
+    }
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        Node::size_of(&self.root)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+    /// Returns the entry at position `index` in key order, in O(log n).
+    pub fn nth(&self, index: usize) -> Option<(&K, &V)> {
+        self.root
+            .as_ref()
+            .and_then(|root| root.select(index))
+            .map(|node| (&node.key, &node.value))
+    }
+    pub fn keys(&self) -> Keys<'_, K, V, A> {
+        Keys { inner: self.iter() }
+    }
+    pub fn values(&self) -> Values<'_, K, V, A> {
+        Values { inner: self.iter() }
+    }
+}
+
+/// Mutable access to values, for maps without a summary (see `Augment`).
+impl<K, V, C> BSTMap<K, V, C> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        let remaining = self.len();
+        let mut stack = VecDeque::new();
+        if let Some(root) = &mut self.root {
+            stack.push_back(StackMutMember::Node(root));
+        }
+        IterMut { stack, remaining }
+    }
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let map: BSTMap<i32, &str> = BSTMap::new();
+        assert_eq!(map, BSTMap::new());
+        assert_eq!(map.get(&1), None);
+        assert!(!map.contains_key(&1));
+    }
+
+    #[test]
+    fn insert_replaces_value() {
+        let mut map = BSTMap::new();
+        assert_eq!(map.insert(10, "ten"), None);
+        assert_eq!(map.insert(20, "twenty"), None);
+        assert_eq!(map.insert(10, "TEN"), Some("ten"));
+        assert_eq!(map.get(&10), Some(&"TEN"));
+        assert_eq!(map.get(&20), Some(&"twenty"));
+        assert_eq!(map.get(&30), None);
+    }
+
+    #[test]
+    fn insert_rebalances() {
+        let mut map = BSTMap::new();
+        map.insert(100, 'a');
+        map.insert(50, 'b');
+        map.insert(20, 'c');
+        assert_eq!(
+            map.root,
+            Node::new_entry(
+                50,
+                'b',
+                Node::new_entry(20, 'c', None, None),
+                Node::new_entry(100, 'a', None, None)
+            )
+        );
+    }
+
+    #[test]
+    fn equality_ignores_shape() {
+        let mut ascending = BSTMap::new();
+        let mut descending = BSTMap::new();
+        for key in 0..5 {
+            ascending.insert(key, 'x');
+            descending.insert(4 - key, 'x');
+        }
+        assert_ne!(ascending.root, descending.root);
+        assert_eq!(ascending, descending);
+
+        descending.insert(4, 'y');
+        assert_ne!(ascending, descending);
+        descending.remove(&4);
+        assert_ne!(ascending, descending);
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut map = BSTMap::new();
+        map.insert("a", 1);
+        map.insert("b", 2);
+        *map.get_mut(&"a").unwrap() += 10;
+        assert_eq!(map.get(&"a"), Some(&11));
+        assert_eq!(map.get_mut(&"c"), None);
+    }
+
+    #[test]
+    fn remove() {
+        let mut map = BSTMap::new();
+        for key in [5, 3, 7, 6, 2, 4, 9, 8, 10, 1] {
+            map.insert(key, key * 10);
+        }
+        assert_eq!(map.remove(&5), Some(50));
+        assert_eq!(map.remove(&5), None);
+        assert_eq!(map.remove_entry(&1), Some((1, 10)));
+        assert_eq!(map.remove(&42), None);
+        assert_eq!(
+            map.keys().copied().collect::<Vec<i32>>(),
+            vec![2, 3, 4, 6, 7, 8, 9, 10]
+        );
+    }
+
+    #[test]
+    fn iterators() {
+        let mut map = BSTMap::new();
+        map.insert(3, "c");
+        map.insert(1, "a");
+        map.insert(2, "b");
+
+        assert_eq!(
+            map.iter().collect::<Vec<(&i32, &&str)>>(),
+            vec![(&1, &"a"), (&2, &"b"), (&3, &"c")]
+        );
+        assert_eq!(map.keys().copied().collect::<Vec<i32>>(), vec![1, 2, 3]);
+        assert_eq!(
+            map.values().copied().collect::<Vec<&str>>(),
+            vec!["a", "b", "c"]
+        );
+
+        for value in map.values_mut() {
+            *value = "x";
+        }
+        for (key, value) in &mut map {
+            if *key == 2 {
+                *value = "y";
+            }
+        }
+        assert_eq!(
+            map.into_iter().collect::<Vec<(i32, &str)>>(),
+            vec![(1, "x"), (2, "y"), (3, "x")]
+        );
+    }
+
+    #[test]
+    fn entry_counts() {
+        let mut map = BSTMap::new();
+        for word in ["b", "a", "c", "a", "b", "a"] {
+            *map.entry(word).or_insert(0) += 1;
+        }
+        assert_eq!(
+            map.iter().collect::<Vec<(&&str, &i32)>>(),
+            vec![(&"a", &3), (&"b", &2), (&"c", &1)]
+        );
+    }
+
+    #[test]
+    fn entry_and_modify() {
+        let mut map = BSTMap::new();
+        map.entry(1).and_modify(|v| *v += 1).or_insert(10);
+        map.entry(1).and_modify(|v| *v += 1).or_insert(10);
+        map.entry(2).or_insert_with(|| 20);
+        map.entry(3).or_insert_with_key(|key| key * 10);
+        *map.entry(4).or_default() += 4;
+        assert_eq!(map.get(&1), Some(&11));
+        assert_eq!(map.get(&2), Some(&20));
+        assert_eq!(map.get(&3), Some(&30));
+        assert_eq!(map.get(&4), Some(&4));
+        assert_eq!(map.entry(5).key(), &5);
+    }
+
+    #[test]
+    fn entry_insert_rebalances() {
+        let mut map = BSTMap::new();
+        map.entry(100).or_insert('a');
+        map.entry(50).or_insert('b');
+        *map.entry(20).or_insert('c') = 'd';
+        assert_eq!(
+            map.root,
+            Node::new_entry(
+                50,
+                'b',
+                Node::new_entry(20, 'd', None, None),
+                Node::new_entry(100, 'a', None, None)
+            )
+        );
+    }
+
+    #[test]
+    fn occupied_entry() {
+        let mut map = BSTMap::new();
+        for key in [5, 3, 7, 6, 2, 4, 9, 8, 10, 1] {
+            map.insert(key, key * 10);
+        }
+        match map.entry(7) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), &7);
+                assert_eq!(entry.get(), &70);
+                assert_eq!(entry.insert(71), 70);
+                assert_eq!(entry.remove_entry(), (7, 71));
+            }
+            Entry::Vacant(_) => panic!("7 should be present"),
+        }
+        match map.entry(5) {
+            Entry::Occupied(entry) => assert_eq!(entry.remove(), 50),
+            Entry::Vacant(_) => panic!("5 should be present"),
+        }
+        match map.entry(5) {
+            Entry::Occupied(_) => panic!("5 should be removed"),
+            Entry::Vacant(entry) => assert_eq!(entry.into_key(), 5),
+        }
+        assert_eq!(
+            map.keys().copied().collect::<Vec<i32>>(),
+            vec![1, 2, 3, 4, 6, 8, 9, 10]
+        );
+    }
+
+    #[test]
+    fn borrowed_lookups() {
+        let mut map: BSTMap<String, usize> = BSTMap::new();
+        map.insert("one".to_string(), 1);
+        map.insert("two".to_string(), 2);
+
+        assert!(map.contains_key("one"));
+        assert_eq!(map.get("two"), Some(&2));
+        assert_eq!(map.get_key_value("two"), Some((&"two".to_string(), &2)));
+        *map.get_mut("one").unwrap() += 10;
+        assert_eq!(map.remove("one"), Some(11));
+        assert_eq!(map.remove_entry("two"), Some(("two".to_string(), 2)));
+        assert_eq!(map.get("two"), None);
+    }
+
+    #[test]
+    fn with_comparator() {
+        let mut map = BSTMap::with_comparator(|a: &String, b: &String| {
+            a.to_lowercase().cmp(&b.to_lowercase())
+        });
+        map.insert("b".to_string(), 1);
+        map.insert("A".to_string(), 2);
+        assert_eq!(map.insert("B".to_string(), 3), Some(1));
+        *map.entry("a".to_string()).or_insert(0) += 10;
+        assert_eq!(
+            map.into_iter().collect::<Vec<(String, i32)>>(),
+            vec![("A".to_string(), 12), ("b".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn range() {
+        let mut map = BSTMap::new();
+        for key in 0..10 {
+            map.insert(key, key * key);
+        }
+        assert_eq!(
+            map.range(3..6).collect::<Vec<(&i32, &i32)>>(),
+            vec![(&3, &9), (&4, &16), (&5, &25)]
+        );
+        assert_eq!(
+            map.range(8..).map(|(key, _)| *key).collect::<Vec<i32>>(),
+            vec![8, 9]
+        );
+        assert_eq!(map.range(20..30).count(), 0);
+    }
+
+    #[test]
+    fn iter_rev() {
+        let mut map = BSTMap::new();
+        for key in [3, 1, 4, 5, 9, 2, 6] {
+            map.insert(key, key * 10);
+        }
+        assert_eq!(
+            map.keys().rev().copied().collect::<Vec<i32>>(),
+            vec![9, 6, 5, 4, 3, 2, 1]
+        );
+        assert_eq!(
+            map.values().rev().copied().collect::<Vec<i32>>(),
+            vec![90, 60, 50, 40, 30, 20, 10]
+        );
+        assert_eq!(
+            map.range(2..5).rev().collect::<Vec<(&i32, &i32)>>(),
+            vec![(&4, &40), (&3, &30), (&2, &20)]
+        );
+        let mut iter = map.iter_mut();
+        *iter.next().unwrap().1 = 0;
+        *iter.next_back().unwrap().1 = 0;
+        assert_eq!(iter.count(), 5);
+        for value in map.values_mut().rev().take(2) {
+            *value += 1;
+        }
+        assert_eq!(
+            map.into_iter().rev().collect::<Vec<(i32, i32)>>(),
+            vec![(9, 1), (6, 61), (5, 50), (4, 40), (3, 30), (2, 20), (1, 0)]
+        );
+    }
+
+    #[test]
+    fn first_last() {
+        let mut map = BSTMap::new();
+        assert_eq!(map.first_key_value(), None);
+        assert_eq!(map.pop_last(), None);
+        for key in [3, 1, 4, 5, 9, 2, 6] {
+            map.insert(key, key * 10);
+        }
+        assert_eq!(map.first_key_value(), Some((&1, &10)));
+        assert_eq!(map.last_key_value(), Some((&9, &90)));
+        assert_eq!(map.pop_first(), Some((1, 10)));
+        assert_eq!(map.pop_last(), Some((9, 90)));
+        assert_eq!(
+            map.keys().copied().collect::<Vec<i32>>(),
+            vec![2, 3, 4, 5, 6]
+        );
+    }
+
+    #[test]
+    fn bounds() {
+        let mut map = BSTMap::new();
+        for key in [10, 20, 30] {
+            map.insert(key, key / 10);
+        }
+        assert_eq!(map.lower_bound(Bound::Included(&20)), Some((&20, &2)));
+        assert_eq!(map.lower_bound(Bound::Excluded(&20)), Some((&30, &3)));
+        assert_eq!(map.upper_bound(Bound::Excluded(&20)), Some((&10, &1)));
+        assert_eq!(map.upper_bound(Bound::Included(&5)), None);
+    }
+
+    #[test]
+    fn order_statistics() {
+        let mut map = BSTMap::new();
+        for key in [50, 10, 40, 20, 30] {
+            map.insert(key, key * 2);
+        }
+        assert_eq!(map.nth(0), Some((&10, &20)));
+        assert_eq!(map.nth(3), Some((&40, &80)));
+        assert_eq!(map.nth(5), None);
+        assert_eq!(map.rank(&30), 2);
+        assert_eq!(map.rank(&35), 3);
+        assert_eq!(map.count_in_range(20..=40), 3);
+    }
+
+    #[test]
+    fn len() {
+        let mut map = BSTMap::new();
+        assert!(map.is_empty());
+        for key in 0..6 {
+            map.insert(key, key);
+        }
+        map.insert(2, 20);
+        assert_eq!(map.len(), 6);
+        map.remove(&2);
+        assert_eq!(map.len(), 5);
+
+        let mut iter_mut = map.iter_mut();
+        iter_mut.next_back();
+        assert_eq!(iter_mut.len(), 4);
+        assert_eq!(map.keys().len(), 5);
+        assert_eq!(map.range(1..4).len(), 2);
+        assert_eq!(map.into_iter().len(), 5);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Max(i32);
+
+    impl<V> Augment<i32, V> for Max {
+        fn summarize(key: &i32, _: &V) -> Max {
+            Max(*key)
+        }
+        fn combine(&self, other: &Max) -> Max {
+            Max(self.0.max(other.0))
+        }
+    }
+
+    #[test]
+    fn fold_range() {
+        let mut map: BSTMap<i32, &str, OrdComparator, Max> = BSTMap::default();
+        for key in [4, 8, 15, 16, 23, 42] {
+            map.insert(key, "x");
+        }
+        map.entry(16).and_modify(|value| *value = "y");
+        assert_eq!(map.summary(), Some(&Max(42)));
+        assert_eq!(map.fold_range(..20), Some(Max(16)));
+        assert_eq!(map.fold_range(5..8), None);
+        map.remove(&42);
+        assert_eq!(map.fold_range(10..), Some(Max(23)));
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct ValueSum(i64);
+
+    impl Augment<i32, i64> for ValueSum {
+        fn summarize(_: &i32, value: &i64) -> ValueSum {
+            ValueSum(*value)
+        }
+        fn combine(&self, other: &ValueSum) -> ValueSum {
+            ValueSum(self.0 + other.0)
+        }
+    }
+
+    #[test]
+    fn summaries_follow_values() {
+        let mut map: BSTMap<i32, i64, OrdComparator, ValueSum> = BSTMap::default();
+        for key in 0..20 {
+            map.insert(key, 1);
+        }
+        map.insert(3, 10);
+        map.entry(7).and_modify(|value| *value += 100);
+        if let Entry::Occupied(mut entry) = map.entry(11) {
+            entry.insert(1000);
+        }
+        if let Entry::Vacant(entry) = map.entry(20) {
+            entry.insert_entry(5).insert(10000);
+        }
+        if let Entry::Occupied(entry) = map.entry(13) {
+            entry.remove();
+        }
+        map.retain(|key, value| {
+            *value *= 2;
+            *key != 0
+        });
+        assert_eq!(
+            map.extract_if(|key, value| {
+                *value += 1;
+                *key == 19
+            })
+            .count(),
+            1
+        );
+
+        let sum =
+            |range: std::ops::Range<i32>| -> i64 { map.range(range).map(|(_, value)| value).sum() };
+        assert_eq!(map.summary(), Some(&ValueSum(sum(0..21))));
+        for (start, end) in [(0, 5), (3, 8), (6, 12), (10, 21), (15, 16)] {
+            assert_eq!(map.fold_range(start..end), Some(ValueSum(sum(start..end))));
+        }
+    }
+
+    #[test]
+    fn split_join_append() {
+        let mut map = BSTMap::new();
+        for key in 0..20 {
+            map.insert(key, key * 10);
+        }
+        let above = map.split_off(&12);
+        assert_eq!(map.len(), 12);
+        assert_eq!(above.first_key_value(), Some((&12, &120)));
+
+        let (below, found, rest) = above.split(&15);
+        assert_eq!(found, Some((15, 150)));
+        assert_eq!(below.keys().copied().collect::<Vec<i32>>(), [12, 13, 14]);
+        let joined = BSTMap::join(below, 15, 1500, rest);
+        assert_eq!(joined.get(&15), Some(&1500));
+        assert_eq!(joined.len(), 8);
+
+        let mut other = BSTMap::new();
+        other.insert(5, 0);
+        other.insert(30, 300);
+        map.append(&mut other);
+        assert!(other.is_empty());
+        assert_eq!(map.get(&5), Some(&0));
+        assert_eq!(map.len(), 13);
+    }
+
+    #[test]
+    fn from_iter_and_extend() {
+        let mut map: BSTMap<i32, &str> = [(3, "c"), (1, "a"), (3, "C"), (2, "b")]
+            .into_iter()
+            .collect();
+        assert_eq!(map.get(&3), Some(&"C"));
+        assert_eq!(map.len(), 3);
+        map.extend([(2, "B"), (4, "d"), (4, "D")]);
+        assert_eq!(
+            map.iter()
+                .map(|(key, value)| (*key, *value))
+                .collect::<Vec<_>>(),
+            [(1, "a"), (2, "B"), (3, "C"), (4, "D")]
+        );
+        assert_eq!(
+            BSTMap::from([(1, 1), (0, 0)]).first_key_value(),
+            Some((&0, &0))
+        );
+    }
+
+    #[test]
+    fn retain_and_extract_if() {
+        let mut map: BSTMap<i32, i32> = (0..20).map(|key| (key, key)).collect();
+        map.retain(|key, value| {
+            *value *= 10;
+            key % 2 == 0
+        });
+        assert_eq!(map.len(), 10);
+        assert_eq!(map.get(&4), Some(&40));
+
+        let expired: Vec<(i32, i32)> = map
+            .extract_if(|_, value| {
+                *value += 1;
+                *value > 110
+            })
+            .collect();
+        assert_eq!(expired, [(12, 121), (14, 141), (16, 161), (18, 181)]);
+        assert_eq!(map.get(&10), Some(&101));
+        assert_eq!(map.len(), 6);
+    }
+
+    #[test]
+    fn cursor_moves_and_wraps() {
+        let map: BSTMap<i32, char> = (0..10)
+            .map(|key| (key * 10, (b'a' + key as u8) as char))
+            .collect();
+        let mut cursor = map.lower_bound_cursor(Bound::Included(&35));
+        assert_eq!(cursor.peek(), Some((&40, &'e')));
+        assert_eq!(cursor.index(), Some(4));
+        assert_eq!(cursor.peek_prev(), Some((&30, &'d')));
+        cursor.move_next();
+        assert_eq!(cursor.peek(), Some((&50, &'f')));
+
+        let mut cursor = map.upper_bound_cursor(Bound::Excluded(&10));
+        assert_eq!(cursor.peek(), Some((&0, &'a')));
+        cursor.move_prev();
+        assert_eq!((cursor.index(), cursor.peek()), (None, None));
+        assert_eq!(cursor.peek_prev(), Some((&90, &'j')));
+        cursor.move_next();
+        assert_eq!(cursor.peek(), Some((&0, &'a')));
+
+        assert_eq!(map.lower_bound_cursor(Bound::Excluded(&90)).index(), None);
+        assert_eq!(map.upper_bound_cursor(Bound::Excluded(&0)).index(), None);
+        let empty: BSTMap<i32, char> = BSTMap::new();
+        let mut cursor = empty.lower_bound_cursor::<i32>(Bound::Unbounded);
+        cursor.move_next();
+        assert_eq!((cursor.index(), cursor.peek_next()), (None, None));
+    }
+
+    #[test]
+    fn cursor_mut_edits() {
+        let mut map: BSTMap<i32, i32> = [(10, 1), (20, 2), (30, 3)].into();
+        let mut cursor = map.lower_bound_cursor_mut(Bound::Included(&20));
+        *cursor.peek().unwrap().1 *= 100;
+        cursor.insert_before(15, 0);
+        cursor.insert_after(25, 0);
+        assert_eq!(cursor.peek(), Some((&20, &mut 200)));
+        assert_eq!(cursor.index(), Some(2));
+
+        assert_eq!(cursor.remove_current(), Some((20, 200)));
+        assert_eq!(cursor.peek(), Some((&25, &mut 0)));
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some((30, 3)));
+        assert_eq!(cursor.index(), None);
+        assert_eq!(cursor.remove_current(), None);
+        cursor.insert_before(40, 4);
+        cursor.insert_after(5, 5);
+        assert_eq!(cursor.peek_next(), Some((&5, &5)));
+        assert_eq!(cursor.peek_prev(), Some((&40, &4)));
+
+        assert!(map
+            .into_iter()
+            .eq([(5, 5), (10, 1), (15, 0), (25, 0), (40, 4)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "inserted key must be ordered around the cursor")]
+    fn cursor_rejects_unordered_insert() {
+        let mut map: BSTMap<i32, ()> = [(10, ()), (20, ())].into();
+        map.lower_bound_cursor_mut(Bound::Included(&20))
+            .insert_before(10, ());
+    }
+}
@@ -0,0 +1,354 @@
+use std::borrow::Borrow;
+use std::iter::{FlatMap, FusedIterator, RepeatN};
+
+use crate::map::{self, BSTMap, Entry};
+use crate::{Augment, Comparator, Node, OrdComparator};
+
+/// The number of occurrences in a subtree, counting each element as often
+/// as it occurs.
+#[derive(Debug, PartialEq)]
+struct Occurrences(usize);
+
+impl<T> Augment<T, usize> for Occurrences {
+    fn summarize(_: &T, count: &usize) -> Occurrences {
+        Occurrences(*count)
+    }
+    fn combine(&self, other: &Occurrences) -> Occurrences {
+        Occurrences(self.0 + other.0)
+    }
+}
+
+impl<T> Node<T, usize, Occurrences> {
+    /// Returns the element at position `index` among all occurrences.
+    fn select_occurrence(&self, mut index: usize) -> Option<&T> {
+        let mut node = self;
+        loop {
+            let before = node.left.as_ref().map_or(0, |left| left.summary.0);
+            if index < before {
+                node = node.left.as_deref()?;
+            } else if index < before + node.value {
+                return Some(&node.key);
+            } else {
+                index -= before + node.value;
+                node = node.right.as_deref()?;
+            }
+        }
+    }
+}
+
+impl<'a, T, C> IntoIterator for &'a BSTMultiset<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the distinct elements of a `BSTMultiset` and their counts.
+pub struct Counts<'a, T> {
+    inner: map::Iter<'a, T, usize, Occurrences>,
+}
+
+impl<'a, T> Iterator for Counts<'a, T> {
+    type Item = (&'a T, &'a usize);
+    fn next(&mut self) -> Option<(&'a T, &'a usize)> {
+        self.inner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Counts<'a, T> {
+    fn next_back(&mut self) -> Option<(&'a T, &'a usize)> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Counts<'a, T> {}
+
+impl<'a, T> FusedIterator for Counts<'a, T> {}
+
+type Repeat<'a, T> = fn((&'a T, &'a usize)) -> RepeatN<&'a T>;
+
+fn repeat<'a, T>((value, count): (&'a T, &'a usize)) -> RepeatN<&'a T> {
+    std::iter::repeat_n(value, *count)
+}
+
+/// In-order iterator over the elements of a `BSTMultiset`, yielding each
+/// element as many times as it occurs.
+pub struct Iter<'a, T> {
+    inner: FlatMap<Counts<'a, T>, RepeatN<&'a T>, Repeat<'a, T>>,
+    remaining: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        let value = self.inner.next()?;
+        self.remaining -= 1;
+        Some(value)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        let value = self.inner.next_back()?;
+        self.remaining -= 1;
+        Some(value)
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> FusedIterator for Iter<'a, T> {}
+
+/// A sorted bag: an ordered set that keeps equal elements, storing one node
+/// per distinct element together with its multiplicity.
+///
+/// Of equal elements only the first one inserted is stored. Every node also
+/// tracks the number of occurrences in its subtree, so `nth` takes O(log n).
+#[derive(Debug)]
+pub struct BSTMultiset<T, C = OrdComparator> {
+    map: BSTMap<T, usize, C, Occurrences>,
+}
+
+/// Multisets are equal when their elements occur equally often.
+impl<T: PartialEq, C> PartialEq for BSTMultiset<T, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+impl<T: Eq, C> Eq for BSTMultiset<T, C> {}
+
+impl<T, C: Comparator<T> + Default> Default for BSTMultiset<T, C> {
+    fn default() -> Self {
+        Self::with_comparator(C::default())
+    }
+}
+
+impl<T: Ord> BSTMultiset<T> {
+    pub fn new() -> BSTMultiset<T> {
+        BSTMultiset::with_comparator(OrdComparator)
+    }
+}
+
+impl<T, C: Comparator<T>> BSTMultiset<T, C> {
+    /// Creates an empty multiset ordered by `comparator` instead of `Ord`.
+    pub fn with_comparator(comparator: C) -> BSTMultiset<T, C> {
+        BSTMultiset {
+            map: BSTMap::with_augment(comparator),
+        }
+    }
+    /// Adds one occurrence of `value`, returning how often it occurs now.
+    pub fn insert(&mut self, value: T) -> usize {
+        match self.map.entry(value) {
+            Entry::Occupied(mut entry) => {
+                let count = entry.get() + 1;
+                entry.insert(count);
+                count
+            }
+            Entry::Vacant(entry) => {
+                entry.insert_only(1);
+                1
+            }
+        }
+    }
+    /// Returns how often `value` occurs.
+    pub fn count<Q: ?Sized>(&self, value: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.map.get(value).copied().unwrap_or(0)
+    }
+    pub fn contains<Q: ?Sized>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.map.contains_key(value)
+    }
+    /// Removes one occurrence of `value`, returning whether there was one.
+    pub fn remove_one<Q: ?Sized>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        let Some(mut entry) = self.map.find_entry(value) else {
+            return false;
+        };
+        match *entry.get() {
+            1 => {
+                entry.remove();
+            }
+            count => {
+                entry.insert(count - 1);
+            }
+        }
+        true
+    }
+    /// Removes every occurrence of `value`, returning how many there were.
+    pub fn remove_all<Q: ?Sized>(&mut self, value: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.map.remove(value).unwrap_or(0)
+    }
+    /// Returns the smallest element.
+    pub fn first(&self) -> Option<&T> {
+        self.map.first_key_value().map(|(value, _)| value)
+    }
+    /// Returns the greatest element.
+    pub fn last(&self) -> Option<&T> {
+        self.map.last_key_value().map(|(value, _)| value)
+    }
+}
+
+impl<T, C> BSTMultiset<T, C> {
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            inner: self.counts().flat_map(repeat as Repeat<'_, T>),
+            remaining: self.len(),
+        }
+    }
+    /// Iterates in order over the distinct elements and their counts.
+    pub fn counts(&self) -> Counts<'_, T> {
+        Counts {
+            inner: self.map.iter(),
+        }
+    }
+    /// Returns the element at position `index` in sorted order, counting
+    /// each occurrence, in O(log n).
+    pub fn nth(&self, index: usize) -> Option<&T> {
+        self.map.root()?.select_occurrence(index)
+    }
+    /// Returns the number of elements, counting each occurrence.
+    pub fn len(&self) -> usize {
+        self.map.summary().map_or(0, |occurrences| occurrences.0)
+    }
+    /// Returns the number of distinct elements.
+    pub fn distinct_len(&self) -> usize {
+        self.map.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl<T, C: Comparator<T> + Default> FromIterator<T> for BSTMultiset<T, C> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut multiset = BSTMultiset::default();
+        multiset.extend(iter);
+        multiset
+    }
+}
+
+impl<T, C: Comparator<T>> Extend<T> for BSTMultiset<T, C> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts() {
+        let mut bag = BSTMultiset::new();
+        assert!(bag.is_empty());
+        assert_eq!(bag.insert(5), 1);
+        assert_eq!(bag.insert(3), 1);
+        assert_eq!(bag.insert(5), 2);
+        assert_eq!(bag.insert(5), 3);
+        assert_eq!(bag.count(&5), 3);
+        assert_eq!(bag.count(&4), 0);
+        assert_eq!(bag.len(), 4);
+        assert_eq!(bag.distinct_len(), 2);
+
+        assert!(bag.remove_one(&5));
+        assert_eq!(bag.count(&5), 2);
+        assert!(!bag.remove_one(&4));
+        assert_eq!(bag.remove_all(&5), 2);
+        assert!(!bag.contains(&5));
+        assert_eq!(bag.remove_all(&5), 0);
+        assert!(bag.remove_one(&3));
+        assert!(bag.is_empty());
+        assert_eq!(bag, BSTMultiset::new());
+    }
+
+    #[test]
+    fn iter_repeats() {
+        let bag: BSTMultiset<i32> = [3, 1, 3, 2, 3, 1].into_iter().collect();
+        assert_eq!(
+            bag.iter().copied().collect::<Vec<i32>>(),
+            [1, 1, 2, 3, 3, 3]
+        );
+        assert_eq!(
+            bag.iter().rev().copied().collect::<Vec<i32>>(),
+            [3, 3, 3, 2, 1, 1]
+        );
+        let mut iter = bag.iter();
+        assert_eq!(iter.len(), 6);
+        iter.next();
+        iter.next_back();
+        assert_eq!(iter.len(), 4);
+        assert_eq!(
+            bag.counts()
+                .map(|(value, count)| (*value, *count))
+                .collect::<Vec<_>>(),
+            [(1, 2), (2, 1), (3, 3)]
+        );
+        assert_eq!((bag.first(), bag.last()), (Some(&1), Some(&3)));
+    }
+
+    #[test]
+    fn nth_counts_occurrences() {
+        let mut bag: BSTMultiset<i32> = (0..40).map(|i| (i * 7) % 13).collect();
+        bag.remove_all(&4);
+        bag.remove_one(&9);
+        let all: Vec<&i32> = bag.iter().collect();
+        for (index, value) in all.iter().enumerate() {
+            assert_eq!(bag.nth(index), Some(*value));
+        }
+        assert_eq!(bag.nth(all.len()), None);
+        assert_eq!(BSTMultiset::<i32>::new().nth(0), None);
+    }
+
+    #[test]
+    fn equality_counts_occurrences() {
+        let ascending: BSTMultiset<i32> = [1, 2, 2, 3, 4].into_iter().collect();
+        let mut descending: BSTMultiset<i32> = [4, 3, 2, 2, 1].into_iter().collect();
+        assert_eq!(ascending, descending);
+        descending.remove_one(&2);
+        descending.insert(3);
+        assert_eq!(ascending.len(), descending.len());
+        assert_ne!(ascending, descending);
+    }
+
+    #[test]
+    fn sliding_window_median() {
+        let values = [5, 1, 4, 4, 9, 2, 7, 7, 3];
+        let mut window = BSTMultiset::new();
+        let mut medians = Vec::new();
+        for (index, value) in values.iter().enumerate() {
+            window.insert(*value);
+            if index >= 3 {
+                window.remove_one(&values[index - 3]);
+            }
+            if index >= 2 {
+                medians.push(*window.nth(1).unwrap());
+            }
+        }
+        assert_eq!(medians, [4, 4, 4, 4, 7, 7, 7]);
+    }
+}
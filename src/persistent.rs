@@ -0,0 +1,477 @@
+use std::borrow::Borrow;
+use std::cmp::{max, Ordering};
+use std::iter::FusedIterator;
+use std::sync::Arc;
+
+use crate::{Comparator, InOrder, OrdComparator, Shape, Subtree};
+
+type Link<T> = Option<Arc<Node<T>>>;
+
+/// An immutable AVL node. Nodes are shared between versions, so updates
+/// build new nodes along the changed path instead of editing these.
+///
+/// Heights and balance factors follow the conventions of the crate's
+/// `Node`: a leaf has height 0 and balance is right minus left.
+#[derive(Debug)]
+struct Node<T> {
+    value: T,
+    left: Link<T>,
+    right: Link<T>,
+    shape: Shape,
+}
+
+impl<T> Node<T> {
+    fn size_of(tree: &Link<T>) -> usize {
+        tree.as_ref().map_or(0, |node| node.shape.size())
+    }
+    /// Height of the tree rooted at `tree`, counting an empty tree as 0 and
+    /// a leaf as 1.
+    fn height_of(tree: &Link<T>) -> u32 {
+        tree.as_ref().map_or(0, |node| node.height() + 1)
+    }
+    /// Height of the subtree rooted here, 0 for a leaf.
+    fn height(&self) -> u32 {
+        self.shape.height()
+    }
+    /// Height of the right subtree minus that of the left one.
+    fn balance_factor(&self) -> i32 {
+        Node::height_of(&self.right) as i32 - Node::height_of(&self.left) as i32
+    }
+    fn new(value: T, left: Link<T>, right: Link<T>) -> Arc<Node<T>> {
+        let height = max(Node::height_of(&left), Node::height_of(&right));
+        let size = 1 + Node::size_of(&left) + Node::size_of(&right);
+        Arc::new(Node {
+            shape: Shape::new(height, size),
+            value,
+            left,
+            right,
+        })
+    }
+    fn find<'a, Q: ?Sized, C: Comparator<Q>>(tree: &'a Link<T>, value: &Q, cmp: &C) -> Option<&'a T>
+    where
+        T: Borrow<Q>,
+    {
+        let mut tree = tree;
+        while let Some(node) = tree {
+            tree = match cmp.compare(value, node.value.borrow()) {
+                Ordering::Less => &node.left,
+                Ordering::Equal => return Some(&node.value),
+                Ordering::Greater => &node.right,
+            };
+        }
+        None
+    }
+}
+
+impl<T: Clone> Node<T> {
+    /// Builds a node from `value` and two subtrees whose heights differ by at
+    /// most two, rotating copies of the taller side's top nodes if needed.
+    fn balance(value: T, left: Link<T>, right: Link<T>) -> Arc<Node<T>> {
+        match Node::height_of(&right) as i32 - Node::height_of(&left) as i32 {
+            -2 => {
+                let left = left.expect("taller subtree is not empty");
+                if left.balance_factor() > 0 {
+                    let pivot = left.right.as_ref().expect("taller subtree is not empty");
+                    let new_left =
+                        Node::new(left.value.clone(), left.left.clone(), pivot.left.clone());
+                    let new_right = Node::new(value, pivot.right.clone(), right);
+                    Node::new(pivot.value.clone(), Some(new_left), Some(new_right))
+                } else {
+                    let right = Node::new(value, left.right.clone(), right);
+                    Node::new(left.value.clone(), left.left.clone(), Some(right))
+                }
+            }
+            2 => {
+                let right = right.expect("taller subtree is not empty");
+                if right.balance_factor() < 0 {
+                    let pivot = right.left.as_ref().expect("taller subtree is not empty");
+                    let new_left = Node::new(value, left, pivot.left.clone());
+                    let new_right = Node::new(
+                        right.value.clone(),
+                        pivot.right.clone(),
+                        right.right.clone(),
+                    );
+                    Node::new(pivot.value.clone(), Some(new_left), Some(new_right))
+                } else {
+                    let left = Node::new(value, left, right.left.clone());
+                    Node::new(right.value.clone(), Some(left), right.right.clone())
+                }
+            }
+            -1..=1 => Node::new(value, left, right),
+            invalid_bf => panic!(
+                "Balance factor should be from interval [-2, 2], but is {}",
+                invalid_bf
+            ),
+        }
+    }
+    /// Returns the tree with `value` added, or `None` if an equal value is
+    /// already present.
+    fn insert<C: Comparator<T>>(tree: &Link<T>, value: T, cmp: &C) -> Option<Arc<Node<T>>> {
+        let node = match tree {
+            None => return Some(Node::new(value, None, None)),
+            Some(node) => node,
+        };
+        match cmp.compare(&value, &node.value) {
+            Ordering::Less => {
+                let left = Node::insert(&node.left, value, cmp)?;
+                Some(Node::balance(
+                    node.value.clone(),
+                    Some(left),
+                    node.right.clone(),
+                ))
+            }
+            Ordering::Equal => None,
+            Ordering::Greater => {
+                let right = Node::insert(&node.right, value, cmp)?;
+                Some(Node::balance(
+                    node.value.clone(),
+                    node.left.clone(),
+                    Some(right),
+                ))
+            }
+        }
+    }
+    /// Returns the tree without `value`, or `None` if it is not present.
+    fn remove<Q: ?Sized, C: Comparator<Q>>(tree: &Link<T>, value: &Q, cmp: &C) -> Option<Link<T>>
+    where
+        T: Borrow<Q>,
+    {
+        let node = tree.as_ref()?;
+        match cmp.compare(value, node.value.borrow()) {
+            Ordering::Less => {
+                let left = Node::remove(&node.left, value, cmp)?;
+                Some(Some(Node::balance(
+                    node.value.clone(),
+                    left,
+                    node.right.clone(),
+                )))
+            }
+            Ordering::Equal => Some(match (&node.left, &node.right) {
+                (None, right) => right.clone(),
+                (left, None) => left.clone(),
+                (left, Some(right)) => {
+                    let (successor, right) = Node::remove_first(right);
+                    Some(Node::balance(successor, left.clone(), right))
+                }
+            }),
+            Ordering::Greater => {
+                let right = Node::remove(&node.right, value, cmp)?;
+                Some(Some(Node::balance(
+                    node.value.clone(),
+                    node.left.clone(),
+                    right,
+                )))
+            }
+        }
+    }
+    /// Returns the smallest value of the tree rooted at `node` and the tree
+    /// without it.
+    fn remove_first(node: &Arc<Node<T>>) -> (T, Link<T>) {
+        match &node.left {
+            None => (node.value.clone(), node.right.clone()),
+            Some(left) => {
+                let (first, left) = Node::remove_first(left);
+                let node = Node::balance(node.value.clone(), left, node.right.clone());
+                (first, Some(node))
+            }
+        }
+    }
+}
+
+impl<'a, T> Subtree for &'a Node<T> {
+    type Item = &'a T;
+    type Key = T;
+    fn item(self) -> &'a T {
+        &self.value
+    }
+    fn key(&self) -> &T {
+        &self.value
+    }
+    fn left(self) -> Option<&'a Node<T>> {
+        self.left.as_deref()
+    }
+    fn right(self) -> Option<&'a Node<T>> {
+        self.right.as_deref()
+    }
+    fn size(self) -> usize {
+        self.shape.size()
+    }
+}
+
+impl<'a, T, C> IntoIterator for &'a PersistentBST<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// In-order iterator over the values of a `PersistentBST`.
+pub struct Iter<'a, T> {
+    inner: InOrder<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+impl<'a, T> FusedIterator for Iter<'a, T> {}
+
+/// An immutable ordered set whose versions share structure.
+///
+/// Nodes are reference counted with `Arc`, so `clone` takes O(1) and the
+/// resulting snapshot can be sent to other threads. `insert` and `remove`
+/// leave `self` untouched and return a new version in O(log n): only the
+/// nodes on the path to the change, and those rotated while rebalancing it,
+/// are copied, and every other subtree is shared with the old version.
+#[derive(Debug)]
+pub struct PersistentBST<T, C = OrdComparator> {
+    root: Link<T>,
+    comparator: C,
+}
+
+/// Versions are equal when they hold equal values, whatever their shape.
+impl<T: PartialEq, C> PartialEq for PersistentBST<T, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq, C> Eq for PersistentBST<T, C> {}
+
+impl<T, C: Clone> Clone for PersistentBST<T, C> {
+    fn clone(&self) -> Self {
+        PersistentBST {
+            root: self.root.clone(),
+            comparator: self.comparator.clone(),
+        }
+    }
+}
+
+impl<T, C: Comparator<T> + Default> Default for PersistentBST<T, C> {
+    fn default() -> Self {
+        PersistentBST::with_comparator(C::default())
+    }
+}
+
+impl<T: Ord> PersistentBST<T> {
+    pub fn new() -> PersistentBST<T> {
+        PersistentBST::with_comparator(OrdComparator)
+    }
+}
+
+impl<T, C: Comparator<T>> PersistentBST<T, C> {
+    /// Creates an empty tree ordered by `comparator` instead of `Ord`.
+    pub fn with_comparator(comparator: C) -> PersistentBST<T, C> {
+        PersistentBST {
+            root: None,
+            comparator,
+        }
+    }
+    pub fn contains<Q: ?Sized>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        self.get(value).is_some()
+    }
+    /// Returns the stored value equal to `value`.
+    pub fn get<Q: ?Sized>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        Node::find(&self.root, value, &self.comparator)
+    }
+}
+
+impl<T: Clone, C: Comparator<T> + Clone> PersistentBST<T, C> {
+    /// Returns a version that also contains `value`.
+    ///
+    /// If an equal value is already present, the returned version is a plain
+    /// clone of `self`.
+    pub fn insert(&self, value: T) -> Self {
+        match Node::insert(&self.root, value, &self.comparator) {
+            Some(root) => PersistentBST {
+                root: Some(root),
+                comparator: self.comparator.clone(),
+            },
+            None => self.clone(),
+        }
+    }
+    /// Returns a version without `value`.
+    ///
+    /// If `value` is not present, the returned version is a plain clone of
+    /// `self`.
+    pub fn remove<Q: ?Sized>(&self, value: &Q) -> Self
+    where
+        T: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        match Node::remove(&self.root, value, &self.comparator) {
+            Some(root) => PersistentBST {
+                root,
+                comparator: self.comparator.clone(),
+            },
+            None => self.clone(),
+        }
+    }
+}
+
+impl<T, C> PersistentBST<T, C> {
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            inner: InOrder::new(self.root.as_deref()),
+        }
+    }
+    /// Returns the number of values in the tree.
+    pub fn len(&self) -> usize {
+        Node::size_of(&self.root)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+    pub fn first(&self) -> Option<&T> {
+        let mut node = self.root.as_deref()?;
+        while let Some(left) = node.left.as_deref() {
+            node = left;
+        }
+        Some(&node.value)
+    }
+    pub fn last(&self) -> Option<&T> {
+        let mut node = self.root.as_deref()?;
+        while let Some(right) = node.right.as_deref() {
+            node = right;
+        }
+        Some(&node.value)
+    }
+    /// Returns whether both versions share the same root, which makes them
+    /// equal without comparing any values.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.root, &other.root) {
+            (Some(root), Some(other)) => Arc::ptr_eq(root, other),
+            (root, other) => root.is_none() && other.is_none(),
+        }
+    }
+}
+
+impl<T: Clone, C: Comparator<T> + Clone + Default> FromIterator<T> for PersistentBST<T, C> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(PersistentBST::default(), |tree, value| tree.insert(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::thread;
+
+    fn assert_balanced<T>(tree: &Link<T>) {
+        if let Some(node) = tree {
+            assert_balanced(&node.left);
+            assert_balanced(&node.right);
+            assert!(node.balance_factor().abs() <= 1);
+            assert_eq!(
+                node.height(),
+                max(Node::height_of(&node.left), Node::height_of(&node.right))
+            );
+            assert_eq!(
+                node.shape.size(),
+                1 + Node::size_of(&node.left) + Node::size_of(&node.right)
+            );
+        }
+    }
+
+    fn nodes<T>(tree: &Link<T>, found: &mut HashSet<*const Node<T>>) {
+        if let Some(node) = tree {
+            found.insert(Arc::as_ptr(node));
+            nodes(&node.left, found);
+            nodes(&node.right, found);
+        }
+    }
+
+    #[test]
+    fn versions_are_independent() {
+        let empty = PersistentBST::new();
+        let small = empty.insert(2).insert(1).insert(3);
+        let large = small.insert(4).remove(&1);
+        assert!(empty.is_empty());
+        assert!(small.iter().copied().eq([1, 2, 3]));
+        assert!(large.iter().copied().eq([2, 3, 4]));
+        assert!(small.contains(&1) && !large.contains(&1));
+        assert_eq!((large.first(), large.last()), (Some(&2), Some(&4)));
+        assert_eq!(large.iter().rev().len(), 3);
+
+        assert!(small.insert(2).ptr_eq(&small));
+        assert!(small.remove(&7).ptr_eq(&small));
+        assert!(!small.insert(0).ptr_eq(&small));
+    }
+
+    #[test]
+    fn updates_copy_only_a_path() {
+        let mut tree: PersistentBST<i32> = (0..1000).collect();
+        assert_balanced(&tree.root);
+        for value in (0..2000).step_by(7) {
+            let next = if value < 1000 {
+                tree.remove(&value)
+            } else {
+                tree.insert(value)
+            };
+            assert_balanced(&next.root);
+
+            let mut old = HashSet::new();
+            let mut new = HashSet::new();
+            nodes(&tree.root, &mut old);
+            nodes(&next.root, &mut new);
+            let copied = new.difference(&old).count();
+            assert!(copied <= 2 * Node::height_of(&tree.root) as usize + 2);
+            tree = next;
+        }
+        let expected = (0..1000)
+            .filter(|value| value % 7 != 0)
+            .chain((1001..2000).step_by(7));
+        assert!(tree.iter().copied().eq(expected));
+    }
+
+    #[test]
+    fn equality_ignores_shape() {
+        let ascending: PersistentBST<i32> = (0..5).collect();
+        let descending: PersistentBST<i32> = (0..5).rev().collect();
+        assert_ne!(
+            ascending.root.as_ref().map(|root| &root.value),
+            descending.root.as_ref().map(|root| &root.value)
+        );
+        assert_eq!(ascending, descending);
+        assert_ne!(ascending, descending.remove(&0).insert(5));
+        assert_eq!(ascending, ascending.insert(2));
+    }
+
+    #[test]
+    fn snapshots_cross_threads() {
+        let mut tree: PersistentBST<u32> = (0..100).collect();
+        let snapshot = tree.clone();
+        let reader = thread::spawn(move || snapshot.iter().sum::<u32>());
+        for value in 100..200 {
+            tree = tree.insert(value).remove(&(value - 100));
+        }
+        assert_eq!(reader.join().unwrap(), (0..100).sum());
+        assert!(tree.iter().copied().eq(100..200));
+    }
+}